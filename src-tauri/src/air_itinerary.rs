@@ -0,0 +1,304 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    capture_first, extract_labeled_value, extract_labeled_value_with_source, join_rows, provenance,
+    transport_deductible_tax_cents, Invoice, InvoiceItem, Money, TextItem,
+    TRANSPORT_TAX_RATE_PERCENT,
+};
+
+// 航空运输电子客票行程单的发票类型
+pub(crate) const AIR_ITINERARY_TYPE: &str = "航空运输电子客票行程单";
+pub(crate) const DIGITAL_AIR_ITINERARY_TYPE: &str = "电子发票（航空运输电子客票行程单）";
+
+// 航段信息
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct FlightSegment {
+    from: String,
+    to: String,
+    flight_number: String,
+    seat_class: String,
+    date: String,
+    time: String,
+}
+
+// 航空运输电子客票行程单信息
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AirItinerary {
+    passenger_name: String,
+    passenger_id: String,
+    ticket_number: String,
+    segments: Vec<FlightSegment>,
+    fare: Money,
+    development_fund: Money,
+    fuel_surcharge: Money,
+    other_taxes: Money,
+    total: Money,
+}
+
+impl AirItinerary {
+    // 导出字段对应的值
+    pub(crate) fn export_value(&self, field: &str) -> Option<String> {
+        let value = match field {
+            "旅客姓名" => self.passenger_name.clone(),
+            "电子客票号码" => self.ticket_number.clone(),
+            "航段" => self
+                .segments
+                .iter()
+                .map(|segment| {
+                    format!(
+                        "{}-{} {} {}",
+                        segment.from, segment.to, segment.flight_number, segment.date
+                    )
+                    .trim()
+                    .to_string()
+                })
+                .collect::<Vec<String>>()
+                .join("；"),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    // 导出金额字段对应的值
+    pub(crate) fn export_amount(&self, field: &str) -> Option<Money> {
+        match field {
+            "票价" => Some(self.fare),
+            "民航发展基金" => Some(self.development_fund),
+            "燃油附加费" => Some(self.fuel_surcharge),
+            _ => None,
+        }
+    }
+
+    // 需要标注来源区域的字段及其值
+    pub(crate) fn source_values(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("passenger_name", self.passenger_name.clone()),
+            ("passenger_id", self.passenger_id.clone()),
+            ("ticket_number", self.ticket_number.clone()),
+            ("fare", self.fare.to_string()),
+            ("development_fund", self.development_fund.to_string()),
+            ("fuel_surcharge", self.fuel_surcharge.to_string()),
+            ("other_taxes", self.other_taxes.to_string()),
+            ("total", self.total.to_string()),
+        ]
+    }
+}
+
+// 判断是否为航空运输电子客票行程单
+pub(crate) fn is_air_itinerary(text_items: &[TextItem]) -> bool {
+    text_items
+        .iter()
+        .any(|item| item.text.contains("客票行程单") || item.text.contains("E-TICKET"))
+}
+
+// 提取标签附近的金额：优先取同一行右侧的数值，其次取标签正下方的数值
+fn extract_amount(text_items: &[TextItem], label: &regex::Regex) -> Money {
+    let label_item = match text_items.iter().find(|item| label.is_match(&item.text)) {
+        Some(item) => item,
+        None => return Money::MISSING,
+    };
+
+    let amount_regex = regex::Regex::new(r"(\d+(\.\d{1,2})?)").unwrap();
+    let value_regex = regex::Regex::new(r"^(CNY)?[¥￥]?\d+(\.\d{1,2})?([A-Z]{2})?$").unwrap();
+
+    // 标签与金额在同一个文本项中，如"票价FARE:CNY1000.00"
+    let inline_text = label.replace(&label_item.text, "").to_string();
+    if let Some(caps) = amount_regex.captures(&inline_text) {
+        return Money::parse(&caps[1]);
+    }
+
+    let same_row = text_items
+        .iter()
+        .filter(|item| {
+            value_regex.is_match(&item.text)
+                && (item.y - label_item.y).abs() <= 6.0
+                && item.x > label_item.x
+        })
+        .min_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
+    let below = || {
+        text_items
+            .iter()
+            .filter(|item| {
+                value_regex.is_match(&item.text)
+                    && item.y > label_item.y + 6.0
+                    && item.y - label_item.y <= 30.0
+                    && (item.x - label_item.x).abs() <= 40.0
+            })
+            .min_by(|a, b| a.y.partial_cmp(&b.y).unwrap())
+    };
+
+    same_row
+        .or_else(below)
+        .and_then(|item| amount_regex.captures(&item.text))
+        .map(|caps| Money::parse(&caps[1]))
+        .unwrap_or(Money::MISSING)
+}
+
+// 提取航段：以"自"开头的行为出发地，之后以"至"开头的行依次为到达地，航班信息位于每段出发地所在行
+fn extract_segments(rows: &[String]) -> Vec<FlightSegment> {
+    let station_regex = regex::Regex::new(r"^(自|至)\s*(FROM|TO)?\s*([\p{Han}A-Z]+)").unwrap();
+    let flight_regex =
+        regex::Regex::new(r"(^|\s)([A-Z0-9]{2})\s*(\d{3,4})\s+([A-Z])?(\s|$)").unwrap();
+    let date_regex = regex::Regex::new(r"(\d{4}-\d{2}-\d{2}|\d{2}[A-Z]{3})").unwrap();
+    let time_regex = regex::Regex::new(r"(\d{2}:?\d{2})\s*$").unwrap();
+
+    let mut segments = Vec::new();
+    let mut departure: Option<FlightSegment> = None;
+
+    for row in rows {
+        let caps = match station_regex.captures(row) {
+            Some(caps) => caps,
+            None => continue,
+        };
+        let station = caps[3].to_string();
+
+        if &caps[1] == "至" {
+            if let Some(mut segment) = departure.take() {
+                segment.to = station.clone();
+                segments.push(segment);
+            }
+            // "VOID"表示行程结束
+            if station == "VOID" {
+                break;
+            }
+        }
+
+        // 当前行的航班信息属于从该站出发的航段
+        let mut segment = FlightSegment {
+            from: station,
+            ..Default::default()
+        };
+        if let Some(flight) = flight_regex.captures(row) {
+            segment.flight_number = format!("{}{}", &flight[2], &flight[3]);
+            segment.seat_class = flight
+                .get(4)
+                .map(|m| m.as_str().to_string())
+                .unwrap_or_default();
+            segment.date = capture_first(std::slice::from_ref(row), &date_regex, 1);
+            segment.time = capture_first(std::slice::from_ref(row), &time_regex, 1);
+            departure = Some(segment);
+        } else if &caps[1] == "自" {
+            departure = Some(segment);
+        }
+    }
+
+    segments
+}
+
+// 解析航空运输电子客票行程单
+pub(crate) fn parse_air_itinerary_fapiao(
+    text_items: &[TextItem],
+    mut invoice: Invoice,
+    page_index: usize,
+) -> Invoice {
+    // 设置页面索引
+    invoice.index = page_index + 1;
+    let invoice_type = if text_items.iter().any(|item| item.text.contains("电子发票")) {
+        DIGITAL_AIR_ITINERARY_TYPE
+    } else {
+        AIR_ITINERARY_TYPE
+    };
+    invoice.title = if page_index == 0 {
+        invoice_type.to_string()
+    } else {
+        format!("{} (第{}页)", invoice_type, page_index + 1)
+    };
+    invoice.invoice_type = invoice_type.to_string();
+
+    let rows = join_rows(text_items);
+    let mut itinerary = AirItinerary::default();
+
+    // 发票号码，纸质行程单使用印刷序号
+    let number_regex = regex::Regex::new(r"^(发票号码|印刷序号)[:：]?").unwrap();
+    invoice.number = provenance::record_value(
+        &mut invoice.sources,
+        "number",
+        extract_labeled_value_with_source(text_items, &number_regex, 150.0)
+            .map_value(|number| number.chars().filter(|c| c.is_ascii_digit()).collect()),
+    );
+
+    // 开票日期，纸质行程单为填开日期
+    let date_regex = regex::Regex::new(r"^(开票日期|填开日期)[:：]?").unwrap();
+    invoice.date = provenance::record_value(
+        &mut invoice.sources,
+        "date",
+        extract_labeled_value_with_source(text_items, &date_regex, 150.0),
+    );
+
+    // 旅客信息与电子客票号码
+    let name_regex = regex::Regex::new(r"^旅客姓名[:：]?").unwrap();
+    itinerary.passenger_name = extract_labeled_value(text_items, &name_regex, 100.0);
+    let id_regex = regex::Regex::new(r"^有效身份证件号码[:：]?").unwrap();
+    itinerary.passenger_id = extract_labeled_value(text_items, &id_regex, 200.0);
+    let ticket_regex = regex::Regex::new(r"^电子客票号码[:：]?").unwrap();
+    itinerary.ticket_number = extract_labeled_value(text_items, &ticket_regex, 150.0)
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '-')
+        .collect();
+
+    // 航段
+    itinerary.segments = extract_segments(&rows);
+
+    // 购买方信息
+    let buyer_name_regex = regex::Regex::new(r"购买方名称[:：]?\s*(\S+)").unwrap();
+    invoice.buyer.name = capture_first(&rows, &buyer_name_regex, 1);
+    let buyer_tax_code_regex =
+        regex::Regex::new(r"统一社会信用代码[:：]?\s*([0-9A-Za-z]{15,20})").unwrap();
+    invoice.buyer.tax_code = capture_first(&rows, &buyer_tax_code_regex, 1);
+
+    // 票价与各项税费
+    itinerary.fare = extract_amount(
+        text_items,
+        &regex::Regex::new(r"^票价(FARE)?[:：]?").unwrap(),
+    );
+    itinerary.development_fund = extract_amount(
+        text_items,
+        &regex::Regex::new(r"^民航发展基金(CAACDEVELOPMENTFUND)?[:：]?").unwrap(),
+    );
+    itinerary.fuel_surcharge = extract_amount(
+        text_items,
+        &regex::Regex::new(r"^燃油附加费(FUELSURCHARGE)?[:：]?").unwrap(),
+    );
+    itinerary.other_taxes = extract_amount(
+        text_items,
+        &regex::Regex::new(r"^其他税费(OTHERTAXES)?[:：]?").unwrap(),
+    );
+    itinerary.total = extract_amount(
+        text_items,
+        &regex::Regex::new(r"^合计(TOTAL)?[:：]?").unwrap(),
+    );
+
+    // 可抵扣税额：(票价 + 燃油附加费) ÷ (1 + 9%) × 9%，民航发展基金不计算抵扣
+    if let Some(fare_cents) = itinerary.fare.cents() {
+        let fuel_cents = itinerary.fuel_surcharge.cents().unwrap_or(0);
+        let tax_cents = transport_deductible_tax_cents(fare_cents + fuel_cents);
+        let total_cents = itinerary.total.cents().unwrap_or(fare_cents + fuel_cents);
+        invoice.total_amount_tax = Money::from_cents(total_cents);
+        invoice.total_tax = Money::from_cents(tax_cents);
+        invoice.total_amount = Money::from_cents(total_cents - tax_cents);
+        invoice.deductible_tax = invoice.total_tax;
+        invoice.deductible = true;
+    }
+
+    let route = itinerary
+        .segments
+        .iter()
+        .map(|segment| format!("{}-{}", segment.from, segment.to))
+        .collect::<Vec<String>>()
+        .join(" ");
+    invoice.items.push(InvoiceItem {
+        name: format!("*运输服务*航空旅客运输 {}", route)
+            .trim()
+            .to_string(),
+        spec: "".to_string(),
+        unit: "".to_string(),
+        quantity: "1".to_string(),
+        price: invoice.total_amount.to_string(),
+        amount: invoice.total_amount,
+        tax_rate: format!("{}%", TRANSPORT_TAX_RATE_PERCENT),
+        tax: invoice.total_tax,
+    });
+
+    invoice.air_itinerary = Some(itinerary);
+    invoice
+}
@@ -0,0 +1,231 @@
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{create_empty_invoice, jobs::Job, parse_file_with_progress, Invoice};
+
+// 批量解析进度事件名称
+pub(crate) const BATCH_PROGRESS_EVENT: &str = "batch-progress";
+
+// 批量解析进度事件类型
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchEventKind {
+    // 开始解析文件
+    Started,
+    // 文件中的一页解析完成
+    PageDone,
+    // 文件解析完成
+    Finished,
+    // 文件读取或解析失败
+    Failed,
+}
+
+// 批量解析进度，每个文件开始、每页完成、文件完成或失败时发送
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BatchProgress {
+    job_id: u64,
+    kind: BatchEventKind,
+    path: String,
+    filename: String,
+    // 当前文件已解析页数与总页数
+    page: usize,
+    page_count: usize,
+    // 当前文件解析出的发票数，仅在解析完成时有效
+    invoice_count: usize,
+    error: String,
+    // 批次中的文件总数、已完成（含失败）的文件数、失败的文件数
+    total_files: usize,
+    finished_files: usize,
+    failed_files: usize,
+}
+
+impl BatchProgress {
+    pub(crate) fn kind(&self) -> BatchEventKind {
+        self.kind
+    }
+
+    pub(crate) fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub(crate) fn error(&self) -> &str {
+        &self.error
+    }
+}
+
+// 文件名，用于发票的文件名字段
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string_lossy().to_string())
+}
+
+// 并行解析多个PDF文件，结果按输入顺序返回，无法解析的文件生成一条解析失败的发票
+// 任务取消后不再开始解析新的文件，已解析的结果仍然返回
+pub(crate) fn parse_files(
+    paths: &[PathBuf],
+    job: &Job,
+    on_progress: impl Fn(BatchProgress) + Sync,
+) -> Vec<Invoice> {
+    let total_files = paths.len();
+    let finished_files = AtomicUsize::new(0);
+    let failed_files = AtomicUsize::new(0);
+
+    let progress = |kind: BatchEventKind, path: &Path| BatchProgress {
+        job_id: job.id(),
+        kind,
+        path: path.to_string_lossy().to_string(),
+        filename: file_name(path),
+        page: 0,
+        page_count: 0,
+        invoice_count: 0,
+        error: "".to_string(),
+        total_files,
+        finished_files: finished_files.load(Ordering::SeqCst),
+        failed_files: failed_files.load(Ordering::SeqCst),
+    };
+
+    paths
+        .par_iter()
+        .map(|path| {
+            if job.is_cancelled() {
+                return Vec::new();
+            }
+            on_progress(progress(BatchEventKind::Started, path));
+
+            let on_page = |page: usize, page_count: usize| {
+                on_progress(BatchProgress {
+                    page,
+                    page_count,
+                    ..progress(BatchEventKind::PageDone, path)
+                });
+            };
+
+            let result = parse_file_with_progress(path, &on_page);
+            job.advance();
+            match result {
+                Ok(invoices) => {
+                    finished_files.fetch_add(1, Ordering::SeqCst);
+                    on_progress(BatchProgress {
+                        invoice_count: invoices.len(),
+                        ..progress(BatchEventKind::Finished, path)
+                    });
+                    invoices
+                }
+                Err(e) => {
+                    failed_files.fetch_add(1, Ordering::SeqCst);
+                    finished_files.fetch_add(1, Ordering::SeqCst);
+                    on_progress(BatchProgress {
+                        error: e.clone(),
+                        ..progress(BatchEventKind::Failed, path)
+                    });
+                    let mut failed_invoice = create_empty_invoice(&file_name(path), "解析失败", 0);
+                    failed_invoice.remark = e;
+                    vec![failed_invoice]
+                }
+            }
+        })
+        .collect::<Vec<Vec<Invoice>>>()
+        .into_iter()
+        .flatten()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::JobKind;
+    use std::sync::Mutex;
+
+    // 只有一页、一行文字的PDF文件
+    fn minimal_pdf() -> Vec<u8> {
+        let content = "BT /F1 12 Tf 20 100 Td (Hello) Tj ET";
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 200 200] /Contents 4 0 R \
+             /Resources << /Font << /F1 5 0 R >> >> >>"
+                .to_string(),
+            format!(
+                "<< /Length {} >>\nstream\n{}\nendstream",
+                content.len(),
+                content
+            ),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".to_string(),
+        ];
+
+        let mut pdf = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.extend(format!("{} 0 obj\n{}\nendobj\n", i + 1, object).bytes());
+        }
+        let xref = pdf.len();
+        pdf.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).bytes());
+        for offset in offsets {
+            pdf.extend(format!("{:010} 00000 n \n", offset).bytes());
+        }
+        pdf.extend(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+                objects.len() + 1,
+                xref
+            )
+            .bytes(),
+        );
+        pdf
+    }
+
+    #[test]
+    fn results_keep_input_order_and_count_failures() {
+        let dir = std::env::temp_dir().join(format!("batch_parse_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.pdf"), minimal_pdf()).unwrap();
+        std::fs::write(dir.join("c.pdf"), minimal_pdf()).unwrap();
+        std::fs::write(dir.join("d.pdf"), "不是PDF文件").unwrap();
+        let paths: Vec<PathBuf> = ["a.pdf", "b.pdf", "c.pdf", "d.pdf"]
+            .iter()
+            .map(|name| dir.join(name))
+            .collect();
+
+        let job = Job::new(1, JobKind::Parse, paths.len());
+        let events = Mutex::new(Vec::new());
+        let invoices = parse_files(&paths, &job, |progress| {
+            events.lock().unwrap().push(progress)
+        });
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let results: Vec<(&str, &str)> = invoices
+            .iter()
+            .map(|invoice| (invoice.filename.as_str(), invoice.status.as_str()))
+            .collect();
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].0, "a.pdf");
+        assert_eq!(results[1], ("b.pdf", "解析失败"));
+        assert_eq!(results[2].0, "c.pdf");
+        assert_eq!(results[3], ("d.pdf", "解析失败"));
+        assert_ne!(results[0].1, "解析失败");
+
+        let events = events.into_inner().unwrap();
+        let failed: Vec<&str> = events
+            .iter()
+            .filter(|event| event.kind() == BatchEventKind::Failed)
+            .map(|event| event.filename.as_str())
+            .collect();
+        assert_eq!(failed.len(), 2);
+        assert!(failed.contains(&"b.pdf") && failed.contains(&"d.pdf"));
+        // 全部文件结束时的进度中，失败的文件按文件计数
+        let last = events
+            .iter()
+            .find(|event| event.finished_files == 4)
+            .unwrap();
+        assert_eq!(last.total_files, 4);
+        assert_eq!(last.failed_files, 2);
+        assert_eq!(serde_json::to_value(job.info()).unwrap()["done"], 4);
+    }
+}
@@ -0,0 +1,4 @@
+// 命令行批量解析发票并导出Excel，不依赖图形界面
+fn main() {
+    std::process::exit(invoice_analysis_lib::cli::run())
+}
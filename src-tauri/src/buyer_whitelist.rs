@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::Invoice;
+
+// 购买方不在白名单中时的发票状态
+pub(crate) const BUYER_MISMATCH_STATUS: &str = "抬头不符";
+
+// 白名单文件保存在应用配置目录下
+const WHITELIST_FILE_NAME: &str = "buyer_whitelist.json";
+// 与 tauri.conf.json 中的 identifier 一致，命令行工具据此定位配置目录
+pub(crate) const APP_IDENTIFIER: &str = "com.invoice-analysis.app";
+
+// 本公司主体信息
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BuyerEntity {
+    name: String,
+    #[serde(default)]
+    tax_code: String,
+}
+
+// 白名单文件路径
+pub(crate) fn whitelist_path(config_dir: &Path) -> PathBuf {
+    config_dir.join(WHITELIST_FILE_NAME)
+}
+
+// 应用默认的白名单文件路径，供命令行工具使用
+pub(crate) fn default_whitelist_path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| whitelist_path(&dir.join(APP_IDENTIFIER)))
+}
+
+// 读取白名单，文件不存在时返回空列表
+pub(crate) fn load_whitelist(path: &Path) -> Result<Vec<BuyerEntity>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = fs::read_to_string(path).map_err(|e| format!("读取抬头白名单失败: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("解析抬头白名单失败: {}", e))
+}
+
+// 保存白名单
+pub(crate) fn save_whitelist(path: &Path, entities: &[BuyerEntity]) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("创建配置目录失败: {}", e))?;
+    }
+    let content = serde_json::to_string_pretty(entities)
+        .map_err(|e| format!("序列化抬头白名单失败: {}", e))?;
+    fs::write(path, content).map_err(|e| format!("保存抬头白名单失败: {}", e))
+}
+
+// 规范化名称：去除空白，全角括号转为半角，英文统一大写
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '（' => '(',
+            '）' => ')',
+            _ => c.to_ascii_uppercase(),
+        })
+        .collect()
+}
+
+// 规范化税号：去除空白，统一大写
+fn normalize_tax_code(tax_code: &str) -> String {
+    tax_code
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase()
+}
+
+// 核对购买方是否为本公司主体，返回不符的原因，符合或未配置白名单时返回空字符串
+pub(crate) fn check_buyer(invoice: &Invoice, whitelist: &[BuyerEntity]) -> String {
+    if whitelist.is_empty() {
+        return "".to_string();
+    }
+
+    let name = normalize_name(&invoice.buyer.name);
+    let tax_code = normalize_tax_code(&invoice.buyer.tax_code);
+    if name.is_empty() && tax_code.is_empty() {
+        return "未识别购买方信息".to_string();
+    }
+
+    let name_matches = |entity: &BuyerEntity| {
+        !entity.name.is_empty() && !name.is_empty() && normalize_name(&entity.name) == name
+    };
+    let tax_code_matches = |entity: &BuyerEntity| {
+        !entity.tax_code.is_empty()
+            && !tax_code.is_empty()
+            && normalize_tax_code(&entity.tax_code) == tax_code
+    };
+
+    // 名称一致，且税号一致或任一方未填写税号
+    if whitelist.iter().any(|entity| {
+        name_matches(entity)
+            && (tax_code_matches(entity) || entity.tax_code.is_empty() || tax_code.is_empty())
+    }) {
+        return "".to_string();
+    }
+
+    if let Some(entity) = whitelist.iter().find(|entity| name_matches(entity)) {
+        return format!(
+            "购买方税号 {} 与白名单中 {} 的税号 {} 不一致",
+            invoice.buyer.tax_code, entity.name, entity.tax_code
+        );
+    }
+
+    if let Some(entity) = whitelist.iter().find(|entity| tax_code_matches(entity)) {
+        return format!(
+            "购买方名称 {} 与白名单中税号 {} 的名称 {} 不一致",
+            invoice.buyer.name, entity.tax_code, entity.name
+        );
+    }
+
+    format!("购买方 {} 不在本公司抬头白名单中", invoice.buyer.name)
+}
+
+// 对解析结果逐张核对购买方
+pub(crate) fn apply_buyer_whitelist(invoices: &mut [Invoice], whitelist: &[BuyerEntity]) {
+    for invoice in invoices.iter_mut() {
+        if invoice.status == "解析失败" {
+            continue;
+        }
+        invoice.buyer_mismatch = check_buyer(invoice, whitelist);
+    }
+}
@@ -0,0 +1,257 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    batch, buyer_whitelist, compute_stats, default_export_fields, duplicate, jobs, layout, ledger,
+    write_workbook,
+};
+
+// 退出码：0 全部解析成功，1 存在解析失败的页面，2 参数或导出错误
+const EXIT_OK: i32 = 0;
+const EXIT_PARSE_FAILED: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+
+const USAGE: &str = "用法: invoice-cli [选项] <PDF文件或目录>...
+
+选项:
+  -o, --output <目录>        导出目录，默认为当前目录
+  -n, --name <文件名>        导出文件名（不含扩展名），默认为 发票数据汇总
+  -f, --fields <字段,...>    导出字段，使用逗号分隔，默认导出除火车票、行程单字段
+                             （乘车人、车次、航段、票价等）以外的全部字段
+  -d, --details              同时导出发票明细表
+  -w, --whitelist <文件>     本公司抬头白名单（JSON），默认使用应用配置目录中的白名单
+      --layouts <目录>       发票版式模板目录，默认使用应用配置目录中的模板
+  -l, --ledger <文件>        发票台账数据库，指定时检查重复并记入台账，默认不使用台账
+  -h, --help                 显示帮助信息
+
+退出码:
+  0  全部解析成功
+  1  存在解析失败的页面
+  2  参数错误或导出失败";
+
+// 命令行参数
+struct CliOptions {
+    inputs: Vec<PathBuf>,
+    output: PathBuf,
+    file_name: String,
+    fields: Option<Vec<String>>,
+    details: bool,
+    whitelist: Option<PathBuf>,
+    layouts: Option<PathBuf>,
+    ledger: Option<PathBuf>,
+}
+
+// 解析命令行参数，返回 None 表示仅显示帮助
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<CliOptions>, String> {
+    let mut options = CliOptions {
+        inputs: Vec::new(),
+        output: PathBuf::from("."),
+        file_name: "发票数据汇总".to_string(),
+        fields: None,
+        details: false,
+        whitelist: None,
+        layouts: None,
+        ledger: None,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-o" | "--output" => {
+                let value = args.next().ok_or("缺少导出目录参数")?;
+                options.output = PathBuf::from(value);
+            }
+            "-n" | "--name" => {
+                options.file_name = args.next().ok_or("缺少导出文件名参数")?;
+            }
+            "-f" | "--fields" => {
+                let value = args.next().ok_or("缺少导出字段参数")?;
+                let fields: Vec<String> = value
+                    .split([',', '，'])
+                    .map(|field| field.trim().to_string())
+                    .filter(|field| !field.is_empty())
+                    .collect();
+                if fields.is_empty() {
+                    return Err("导出字段不能为空".to_string());
+                }
+                options.fields = Some(fields);
+            }
+            "-d" | "--details" => options.details = true,
+            "-w" | "--whitelist" => {
+                let value = args.next().ok_or("缺少抬头白名单参数")?;
+                options.whitelist = Some(PathBuf::from(value));
+            }
+            "--layouts" => {
+                let value = args.next().ok_or("缺少版式模板目录参数")?;
+                options.layouts = Some(PathBuf::from(value));
+            }
+            "-l" | "--ledger" => {
+                let value = args.next().ok_or("缺少发票台账参数")?;
+                options.ledger = Some(PathBuf::from(value));
+            }
+            _ if arg.starts_with('-') => return Err(format!("未知参数: {}", arg)),
+            _ => options.inputs.push(PathBuf::from(arg)),
+        }
+    }
+
+    if options.inputs.is_empty() {
+        return Err("请指定PDF文件或目录".to_string());
+    }
+
+    Ok(Some(options))
+}
+
+// 展开输入路径，目录下的PDF文件按文件名排序
+fn collect_pdf_files(inputs: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let is_pdf = |path: &Path| {
+        path.extension()
+            .map(|ext| ext.eq_ignore_ascii_case("pdf"))
+            .unwrap_or(false)
+    };
+
+    let mut files = Vec::new();
+    for input in inputs {
+        if input.is_dir() {
+            let entries = std::fs::read_dir(input)
+                .map_err(|e| format!("无法读取目录 {}: {}", input.display(), e))?;
+            let mut dir_files: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && is_pdf(path))
+                .collect();
+            dir_files.sort();
+            files.extend(dir_files);
+        } else if input.is_file() {
+            files.push(input.clone());
+        } else {
+            return Err(format!("文件或目录不存在: {}", input.display()));
+        }
+    }
+
+    Ok(files)
+}
+
+// 命令行入口，返回进程退出码
+pub fn run() -> i32 {
+    let options = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return EXIT_OK;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, USAGE);
+            return EXIT_ERROR;
+        }
+    };
+
+    let files = match collect_pdf_files(&options.inputs) {
+        Ok(files) if files.is_empty() => {
+            eprintln!("没有找到PDF文件");
+            return EXIT_ERROR;
+        }
+        Ok(files) => files,
+        Err(e) => {
+            eprintln!("{}", e);
+            return EXIT_ERROR;
+        }
+    };
+
+    let whitelist_path = options
+        .whitelist
+        .clone()
+        .or_else(buyer_whitelist::default_whitelist_path);
+    let whitelist = match whitelist_path {
+        Some(path) => match buyer_whitelist::load_whitelist(&path) {
+            Ok(whitelist) => whitelist,
+            Err(e) => {
+                eprintln!("{}", e);
+                return EXIT_ERROR;
+            }
+        },
+        None => Vec::new(),
+    };
+
+    // 版式模板需在解析前读取，有错误的模板文件被跳过
+    let layouts_dir = options.layouts.clone().or_else(layout::default_layout_dir);
+    if let Some(dir) = layouts_dir {
+        let (templates, errors) = layout::load_templates(&dir);
+        for e in errors {
+            eprintln!("{}", e);
+        }
+        layout::set_user_templates(templates);
+    }
+
+    // 台账只在明确指定时使用，避免定时任务写入桌面应用的台账
+    let mut ledger = match &options.ledger {
+        Some(path) => match ledger::Ledger::open(path) {
+            Ok(ledger) => Some(ledger),
+            Err(e) => {
+                eprintln!("{}", e);
+                return EXIT_ERROR;
+            }
+        },
+        None => None,
+    };
+    let batch = ledger::new_batch();
+
+    let job = jobs::Job::new(1, jobs::JobKind::Parse, files.len());
+    let mut invoices = batch::parse_files(&files, &job, |progress| {
+        if progress.kind() == batch::BatchEventKind::Failed {
+            eprintln!("解析文件失败 {}: {}", progress.path(), progress.error());
+        }
+    });
+
+    buyer_whitelist::apply_buyer_whitelist(&mut invoices, &whitelist);
+    if let Err(e) = duplicate::apply_duplicate_detection(&mut invoices, ledger.as_ref(), &batch) {
+        eprintln!("{}", e);
+        return EXIT_ERROR;
+    }
+    let stats = compute_stats(&invoices);
+
+    let fields_to_export = options.fields.unwrap_or_else(default_export_fields);
+    match write_workbook(
+        &invoices,
+        &options.output,
+        &options.file_name,
+        options.details,
+        &fields_to_export,
+        None,
+    ) {
+        Ok(excel_path) => println!("已导出: {}", excel_path.display()),
+        Err(e) => {
+            eprintln!("{}", e);
+            return EXIT_ERROR;
+        }
+    }
+
+    // 导出成功后将正常发票记入台账
+    if let Some(ledger) = ledger.as_mut() {
+        match ledger.record(&invoices, &batch) {
+            Ok(count) => println!("已记入发票台账: {} 张（批次 {}）", count, batch),
+            Err(e) => {
+                eprintln!("{}", e);
+                return EXIT_ERROR;
+            }
+        }
+    }
+
+    println!(
+        "共 {} 个文件，{} 张发票：正常 {}，需复核 {}，抬头不符 {}，重复 {}，疑似重复 {}，解析失败 {}，红字发票 {}，合计金额 {}，合计税额 {}",
+        files.len(),
+        stats.invoice_count,
+        stats.success_count,
+        stats.review_count,
+        stats.buyer_mismatch_count,
+        stats.duplicate_count,
+        stats.suspected_duplicate_count,
+        stats.fail_count,
+        stats.red_letter_count,
+        stats.total_amount,
+        stats.total_tax
+    );
+
+    if stats.fail_count > 0 {
+        EXIT_PARSE_FAILED
+    } else {
+        EXIT_OK
+    }
+}
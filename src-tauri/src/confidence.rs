@@ -0,0 +1,211 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, sync::OnceLock};
+
+use crate::{
+    corrections, dates, digital_invoice,
+    validation::{self, Severity},
+    Invoice,
+};
+
+// 解析器未记录来源的值（如由票价计算得出的金额）的来源得分
+const UNSOURCED_SCORE: f64 = 0.6;
+// 格式不符时的得分系数
+const FORMAT_MISMATCH_FACTOR: f64 = 0.5;
+// 校验错误、警告涉及字段的得分系数
+const CHECK_ERROR_FACTOR: f64 = 0.5;
+const CHECK_WARNING_FACTOR: f64 = 0.8;
+
+// 字段格式的正则，每张发票的每个字段都要检查，首次使用时编译
+static CODE_REGEX: OnceLock<regex::Regex> = OnceLock::new();
+static NUMBER_REGEX: OnceLock<regex::Regex> = OnceLock::new();
+static MACHINE_NUMBER_REGEX: OnceLock<regex::Regex> = OnceLock::new();
+static NAME_REGEX: OnceLock<regex::Regex> = OnceLock::new();
+static TAX_RATE_REGEX: OnceLock<regex::Regex> = OnceLock::new();
+
+// 置信度（0~1）：overall 为关键字段的平均值，fields 为各字段的置信度，键与来源区域一致
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Confidence {
+    overall: f64,
+    fields: BTreeMap<String, f64>,
+}
+
+impl Confidence {
+    pub(crate) fn overall(&self) -> f64 {
+        self.overall
+    }
+}
+
+// 保留两位小数
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+// 值的格式得分：符合该字段的格式时为1
+fn format_score(invoice: &Invoice, field: &str, value: &str) -> f64 {
+    let matches = |regex: &OnceLock<regex::Regex>, pattern: &str| {
+        regex
+            .get_or_init(|| regex::Regex::new(pattern).unwrap())
+            .is_match(value)
+    };
+    let tax_code_score = |status: &str| match status {
+        "有效" => 1.0,
+        "校验位错误" => 0.5,
+        _ => 0.3,
+    };
+
+    let matched = match field {
+        "code" => matches(&CODE_REGEX, r"^\d{10,12}$"),
+        "number" => matches(&NUMBER_REGEX, r"^\d{8,20}$"),
+        "date" => dates::parse_date(value).is_some(),
+        "checksum" => {
+            let digits: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+            digits.len() == 20 && digits.chars().all(|c| c.is_ascii_digit())
+        }
+        "machine_number" => matches(&MACHINE_NUMBER_REGEX, r"^\d{9,12}$"),
+        "buyer.tax_code" => return tax_code_score(&invoice.buyer.tax_code_status),
+        "seller.tax_code" => return tax_code_score(&invoice.seller.tax_code_status),
+        "buyer.name" | "seller.name" => {
+            value.chars().count() >= 2 && matches(&NAME_REGEX, r"\p{Han}")
+        }
+        _ if field.ends_with(".tax_rate") => {
+            matches(&TAX_RATE_REGEX, r"^\d+(\.\d+)?%$")
+                || value.contains("免税")
+                || value.contains("不征税")
+        }
+        _ => true,
+    };
+
+    if matched {
+        1.0
+    } else {
+        FORMAT_MISMATCH_FACTOR
+    }
+}
+
+// 交叉校验得分：校验错误或警告涉及该字段时降低
+fn check_score(invoice: &Invoice, field: &str) -> f64 {
+    invoice
+        .validation
+        .iter()
+        .filter(|finding| finding.fields().iter().any(|name| name == field))
+        .map(|finding| match finding.severity() {
+            Severity::Error => CHECK_ERROR_FACTOR,
+            Severity::Warning => CHECK_WARNING_FACTOR,
+        })
+        .fold(1.0, f64::min)
+}
+
+// 来源得分：按标签提取时取标签匹配程度；客票的金额、税额由价税合计计算得出，与价税合计一致
+fn source_score(invoice: &Invoice, field: &str) -> f64 {
+    if let Some(source) = invoice.sources.get(field) {
+        return source.label_score();
+    }
+    let is_ticket = invoice.train_ticket.is_some() || invoice.air_itinerary.is_some();
+    if is_ticket && (field == "total_amount" || field == "total_tax") {
+        return source_score(invoice, "total_amount_tax");
+    }
+    UNSOURCED_SCORE
+}
+
+// 计算各字段及整张发票的置信度，综合标签匹配程度、与标签的距离、格式和交叉校验结果
+// 人工修正过的字段只考虑交叉校验结果
+pub(crate) fn evaluate(invoice: &Invoice) -> Confidence {
+    let is_ticket = invoice.train_ticket.is_some() || invoice.air_itinerary.is_some();
+    let is_fully_digital = invoice.invoice_type == digital_invoice::FULLY_DIGITAL_NORMAL_TYPE
+        || invoice.invoice_type == digital_invoice::FULLY_DIGITAL_SPECIAL_TYPE;
+
+    // (字段, 值, 是否为关键字段)，关键字段缺失时置信度为0并计入整体置信度
+    let mut values: Vec<(String, String, bool)> = vec![
+        (
+            "code".to_string(),
+            invoice.code.clone(),
+            !is_ticket && !is_fully_digital,
+        ),
+        ("number".to_string(), invoice.number.clone(), true),
+        ("date".to_string(), invoice.date.clone(), true),
+        ("checksum".to_string(), invoice.checksum.clone(), false),
+        (
+            "machine_number".to_string(),
+            invoice.machine_number.clone(),
+            false,
+        ),
+        ("buyer.name".to_string(), invoice.buyer.name.clone(), true),
+        (
+            "buyer.tax_code".to_string(),
+            invoice.buyer.tax_code.clone(),
+            false,
+        ),
+        (
+            "seller.name".to_string(),
+            invoice.seller.name.clone(),
+            !is_ticket,
+        ),
+        (
+            "seller.tax_code".to_string(),
+            invoice.seller.tax_code.clone(),
+            !is_ticket,
+        ),
+        (
+            "total_amount".to_string(),
+            invoice.total_amount.to_string(),
+            true,
+        ),
+        ("total_tax".to_string(), invoice.total_tax.to_string(), true),
+        (
+            "total_amount_tax".to_string(),
+            invoice.total_amount_tax.to_string(),
+            true,
+        ),
+    ];
+    for (i, item) in invoice.items.iter().enumerate() {
+        values.push((format!("items[{}].name", i), item.name.clone(), false));
+        values.push((
+            format!("items[{}].amount", i),
+            item.amount.to_string(),
+            false,
+        ));
+        values.push((
+            format!("items[{}].tax_rate", i),
+            item.tax_rate.clone(),
+            false,
+        ));
+        values.push((format!("items[{}].tax", i), item.tax.to_string(), false));
+    }
+
+    let mut fields = BTreeMap::new();
+    let mut key_scores = Vec::new();
+    for (field, value, is_key) in values {
+        let score = if value.trim().is_empty() {
+            if !is_key {
+                continue;
+            }
+            0.0
+        } else if corrections::is_corrected(invoice, &field) {
+            check_score(invoice, &field)
+        } else {
+            source_score(invoice, &field)
+                * format_score(invoice, &field, value.trim())
+                * check_score(invoice, &field)
+        };
+        let score = round2(score);
+        if is_key {
+            key_scores.push(score);
+        }
+        fields.insert(field, score);
+    }
+
+    let mut overall = if key_scores.is_empty() {
+        0.0
+    } else {
+        key_scores.iter().sum::<f64>() / key_scores.len() as f64
+    };
+    // 存在需要复核的校验错误时，整体置信度不高于最低的关键字段
+    if validation::needs_review(&invoice.validation) {
+        overall = key_scores.iter().copied().fold(overall, f64::min);
+    }
+
+    Confidence {
+        overall: round2(overall),
+        fields,
+    }
+}
@@ -0,0 +1,277 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{derive_deductible, money::Money, Invoice};
+
+// 一次人工修正记录，item 为明细行下标（从0开始），修正发票字段时为 None
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Correction {
+    #[serde(default)]
+    item: Option<usize>,
+    field: String,
+    // 解析得到的原始值，同一字段多次修正时保留最初的值
+    original: String,
+    value: String,
+    time: String,
+    user: String,
+}
+
+// 人工修正日志中的一条，附带所属发票信息
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CorrectionEntry {
+    index: usize,
+    filename: String,
+    number: String,
+    #[serde(flatten)]
+    correction: Correction,
+}
+
+// 字段中文名，用于导出
+pub(crate) fn field_label(field: &str) -> &str {
+    match field {
+        "type" => "发票类型",
+        "code" => "发票代码",
+        "number" => "发票号码",
+        "date" => "开票日期",
+        "remark" => "备注",
+        "buyer.name" => "购买方名称",
+        "buyer.tax_code" => "购买方税号",
+        "seller.name" => "销售方名称",
+        "seller.tax_code" => "销售方税号",
+        "total_amount" => "金额",
+        "total_tax" => "税额",
+        "total_amount_tax" => "价税合计",
+        "name" => "项目名称",
+        "spec" => "规格型号",
+        "unit" => "单位",
+        "quantity" => "数量",
+        "price" => "单价",
+        "amount" => "金额",
+        "tax_rate" => "税率",
+        "tax" => "税额",
+        _ => field,
+    }
+}
+
+// 字段路径，与来源区域的键一致，如 "number"、"items[0].amount"
+pub(crate) fn field_path(item: Option<usize>, field: &str) -> String {
+    match item {
+        Some(item) => format!("items[{}].{}", item, field),
+        None => field.to_string(),
+    }
+}
+
+// 字段是否经过人工修正
+pub(crate) fn is_corrected(invoice: &Invoice, path: &str) -> bool {
+    invoice
+        .corrections
+        .iter()
+        .any(|correction| field_path(correction.item, &correction.field) == path)
+}
+
+// 当前系统用户名，未指定修正人时使用
+pub(crate) fn current_user() -> String {
+    std::env::var("USERNAME")
+        .or_else(|_| std::env::var("USER"))
+        .unwrap_or_default()
+}
+
+// 可修正的字段
+enum FieldRef<'a> {
+    Text(&'a mut String),
+    Amount(&'a mut Money),
+}
+
+fn field_ref<'a>(
+    invoice: &'a mut Invoice,
+    item: Option<usize>,
+    field: &str,
+) -> Result<FieldRef<'a>, String> {
+    if let Some(item_index) = item {
+        let item = invoice
+            .items
+            .get_mut(item_index)
+            .ok_or_else(|| format!("明细行不存在: {}", item_index + 1))?;
+        return match field {
+            "name" => Ok(FieldRef::Text(&mut item.name)),
+            "spec" => Ok(FieldRef::Text(&mut item.spec)),
+            "unit" => Ok(FieldRef::Text(&mut item.unit)),
+            "quantity" => Ok(FieldRef::Text(&mut item.quantity)),
+            "price" => Ok(FieldRef::Text(&mut item.price)),
+            "amount" => Ok(FieldRef::Amount(&mut item.amount)),
+            "tax_rate" => Ok(FieldRef::Text(&mut item.tax_rate)),
+            "tax" => Ok(FieldRef::Amount(&mut item.tax)),
+            _ => Err(format!("不支持修正的明细字段: {}", field)),
+        };
+    }
+
+    match field {
+        "type" => Ok(FieldRef::Text(&mut invoice.invoice_type)),
+        "code" => Ok(FieldRef::Text(&mut invoice.code)),
+        "number" => Ok(FieldRef::Text(&mut invoice.number)),
+        "date" => Ok(FieldRef::Text(&mut invoice.date)),
+        "remark" => Ok(FieldRef::Text(&mut invoice.remark)),
+        "buyer.name" => Ok(FieldRef::Text(&mut invoice.buyer.name)),
+        "buyer.tax_code" => Ok(FieldRef::Text(&mut invoice.buyer.tax_code)),
+        "seller.name" => Ok(FieldRef::Text(&mut invoice.seller.name)),
+        "seller.tax_code" => Ok(FieldRef::Text(&mut invoice.seller.tax_code)),
+        "total_amount" => Ok(FieldRef::Amount(&mut invoice.total_amount)),
+        "total_tax" => Ok(FieldRef::Amount(&mut invoice.total_tax)),
+        "total_amount_tax" => Ok(FieldRef::Amount(&mut invoice.total_amount_tax)),
+        _ => Err(format!("不支持修正的字段: {}", field)),
+    }
+}
+
+// 设置发票字段但不记录修正日志，用于版式模板中按区域提取的字段
+pub(crate) fn assign_field(invoice: &mut Invoice, field: &str, value: &str) -> Result<(), String> {
+    match field_ref(invoice, None, field)? {
+        FieldRef::Text(text) => *text = value.to_string(),
+        FieldRef::Amount(amount) => {
+            let parsed = Money::parse(value);
+            if parsed.is_missing() && !value.is_empty() {
+                return Err(format!("金额格式错误: {}", value));
+            }
+            *amount = parsed;
+        }
+    }
+    Ok(())
+}
+
+// 修正发票或明细行的一个字段并记录修正日志，返回值是否变化，未变化时不记录
+pub(crate) fn apply_correction(
+    invoice: &mut Invoice,
+    item: Option<usize>,
+    field: &str,
+    value: &str,
+    user: &str,
+) -> Result<bool, String> {
+    let value = value.trim();
+    let (previous, value) = match field_ref(invoice, item, field)? {
+        FieldRef::Text(text) => {
+            if text == value {
+                return Ok(false);
+            }
+            (
+                std::mem::replace(text, value.to_string()),
+                value.to_string(),
+            )
+        }
+        FieldRef::Amount(amount) => {
+            let parsed = Money::parse(value);
+            if parsed.is_missing() && !value.is_empty() {
+                return Err(format!("金额格式错误: {}", value));
+            }
+            if *amount == parsed {
+                return Ok(false);
+            }
+            (
+                std::mem::replace(amount, parsed).to_string(),
+                parsed.to_string(),
+            )
+        }
+    };
+
+    // 可抵扣标记和可抵扣税额随发票类型、税额变化
+    if item.is_none() && matches!(field, "type" | "total_tax") {
+        derive_deductible(invoice);
+    }
+
+    let original = invoice
+        .corrections
+        .iter()
+        .find(|correction| correction.item == item && correction.field == field)
+        .map(|correction| correction.original.clone())
+        .unwrap_or(previous);
+    invoice.corrections.push(Correction {
+        item,
+        field: field.to_string(),
+        original,
+        value,
+        time: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+        user: user.to_string(),
+    });
+    Ok(true)
+}
+
+// 单张发票的修正说明，同一字段多次修正时只列出最终值，如"发票号码: 12345678 -> 12345679"
+pub(crate) fn summary(invoice: &Invoice) -> String {
+    let corrections = &invoice.corrections;
+    corrections
+        .iter()
+        .enumerate()
+        .filter(|(i, correction)| {
+            !corrections[i + 1..]
+                .iter()
+                .any(|later| later.item == correction.item && later.field == correction.field)
+        })
+        .map(|(_, correction)| {
+            let label = match correction.item {
+                Some(item) => format!("第{}行{}", item + 1, field_label(&correction.field)),
+                None => field_label(&correction.field).to_string(),
+            };
+            format!("{}: {} -> {}", label, correction.original, correction.value)
+        })
+        .collect::<Vec<String>>()
+        .join("；")
+}
+
+// 所有发票的人工修正日志，按发票序号和修正顺序排列
+pub(crate) fn collect_corrections(invoices: &[Invoice]) -> Vec<CorrectionEntry> {
+    invoices
+        .iter()
+        .flat_map(|invoice| {
+            invoice
+                .corrections
+                .iter()
+                .map(move |correction| CorrectionEntry {
+                    index: invoice.index,
+                    filename: invoice.filename.clone(),
+                    number: invoice.number.clone(),
+                    correction: correction.clone(),
+                })
+        })
+        .collect()
+}
+
+impl CorrectionEntry {
+    // 导出到人工修正工作表的一行
+    pub(crate) fn export_row(&self) -> [String; 9] {
+        [
+            self.index.to_string(),
+            self.filename.clone(),
+            self.number.clone(),
+            self.correction
+                .item
+                .map(|item| (item + 1).to_string())
+                .unwrap_or_default(),
+            field_label(&self.correction.field).to_string(),
+            self.correction.original.clone(),
+            self.correction.value.clone(),
+            self.correction.time.clone(),
+            self.correction.user.clone(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_empty_invoice;
+
+    #[test]
+    fn correction_updates_deductible_tax() {
+        let mut invoice = create_empty_invoice("a.pdf", "正常", 0);
+        invoice.invoice_type = "增值税电子普通发票".to_string();
+        invoice.total_tax = Money::parse("6.00");
+
+        assert!(apply_correction(&mut invoice, None, "type", "增值税专用发票", "张三").unwrap());
+        assert!(invoice.deductible);
+        assert_eq!(invoice.deductible_tax, Money::parse("6.00"));
+
+        assert!(apply_correction(&mut invoice, None, "total_tax", "6.50", "张三").unwrap());
+        assert_eq!(invoice.deductible_tax, Money::parse("6.50"));
+
+        assert!(apply_correction(&mut invoice, None, "type", "普通发票", "张三").unwrap());
+        assert!(!invoice.deductible);
+        assert!(invoice.deductible_tax.is_missing());
+    }
+}
@@ -0,0 +1,76 @@
+use chrono::{Datelike, NaiveDate};
+
+use crate::Invoice;
+
+// 导出时日期单元格的显示格式
+pub(crate) const EXCEL_DATE_FORMAT: &str = "yyyy-mm-dd";
+
+// 解析开票日期文本，兼容 pdf.js 拆分文本项产生的空格和全角数字
+// 如"2024年05月06日"、"2024 年 5 月 6 日"、"2024-05-06"、"2024/5/6"、"2024.05.06"、"20240506"
+pub(crate) fn parse_date(text: &str) -> Option<NaiveDate> {
+    let text: String = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c {
+            '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap_or(c),
+            _ => c,
+        })
+        .collect();
+
+    let separated_regex =
+        regex::Regex::new(r"(\d{4})[年\-/.](\d{1,2})[月\-/.](\d{1,2})(?:日|\D|$)").unwrap();
+    let compact_regex = regex::Regex::new(r"(?:^|\D)(\d{4})(\d{2})(\d{2})(?:\D|$)").unwrap();
+    let caps = separated_regex
+        .captures(&text)
+        .or_else(|| compact_regex.captures(&text))?;
+
+    let year: i32 = caps[1].parse().ok()?;
+    let month: u32 = caps[2].parse().ok()?;
+    let day: u32 = caps[3].parse().ok()?;
+    NaiveDate::from_ymd_opt(year, month, day)
+}
+
+// 发票的开票日期，前端传回的旧数据没有解析后的日期时按文本解析
+pub(crate) fn invoice_date(invoice: &Invoice) -> Option<NaiveDate> {
+    invoice.date_value.or_else(|| parse_date(&invoice.date))
+}
+
+// 转换为 Excel 日期
+pub(crate) fn to_excel_date(date: NaiveDate) -> Option<rust_xlsxwriter::ExcelDateTime> {
+    rust_xlsxwriter::ExcelDateTime::from_ymd(
+        u16::try_from(date.year()).ok()?,
+        date.month() as u8,
+        date.day() as u8,
+    )
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(year, month, day)
+    }
+
+    #[test]
+    fn parse_date_formats() {
+        assert_eq!(parse_date("2024年05月06日"), date(2024, 5, 6));
+        assert_eq!(parse_date("2024 年 5 月 6 日"), date(2024, 5, 6));
+        assert_eq!(parse_date("２０２４年０５月０６日"), date(2024, 5, 6));
+        assert_eq!(parse_date("2024-05-06"), date(2024, 5, 6));
+        assert_eq!(parse_date("2024/5/6"), date(2024, 5, 6));
+        assert_eq!(parse_date("2024.05.06"), date(2024, 5, 6));
+        assert_eq!(parse_date("20240506"), date(2024, 5, 6));
+        assert_eq!(parse_date("开票日期：2024年05月06日"), date(2024, 5, 6));
+    }
+
+    #[test]
+    fn parse_date_rejects_invalid_dates() {
+        assert_eq!(parse_date("2024年02月30日"), None);
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("20240230"), None);
+        assert_eq!(parse_date(""), None);
+        assert_eq!(parse_date("2024年05月"), None);
+    }
+}
@@ -0,0 +1,630 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tauri_plugin_dialog::DialogExt;
+use tauri_plugin_opener::OpenerExt;
+use tauri_plugin_updater::UpdaterExt;
+// use tauri_plugin_notification::NotificationExt;
+
+use crate::{
+    batch, buyer_whitelist, check_invoice, compute_stats, corrections, default_export_fields,
+    duplicate, jobs, layout, ledger, parse_file, parse_pages, provenance, query, write_workbook,
+    Invoice, ProcessingStats, TextItem,
+};
+
+// 定义一个全局静态变量来存储 AppHandle
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+#[derive(Default)]
+struct ProcessingState {
+    invoices: Vec<Invoice>,
+    stats: ProcessingStats,
+    // 本公司抬头白名单
+    buyer_whitelist: Vec<buyer_whitelist::BuyerEntity>,
+    // 当前发票的重复组
+    duplicate_groups: Vec<duplicate::DuplicateGroup>,
+    // 发票台账，打开失败时为 None
+    ledger: Option<ledger::Ledger>,
+    // 当前导入批次
+    batch: String,
+    // 批量解析和导出任务
+    jobs: jobs::JobRegistry,
+}
+
+type AppState = Arc<Mutex<ProcessingState>>;
+
+// 解析PDF提取的文本位置信息
+#[tauri::command]
+async fn parse_invoice_text(
+    filename: &str,
+    pages_text_items: Vec<Vec<TextItem>>,
+    file_hash: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<Invoice>, String> {
+    // println!("接收到前端发来的文本解析请求: {}", filename);
+    // println!("共 {} 页文本数据", pages_text_items.len());
+
+    // 检查输入数据
+    if pages_text_items.is_empty() {
+        return Err("没有接收到文本数据".to_string());
+    }
+
+    let mut invoices = parse_pages(filename, &pages_text_items);
+    if let Some(file_hash) = file_hash {
+        for invoice in &mut invoices {
+            invoice.file_hash = file_hash.clone();
+        }
+    }
+    check_buyers(&mut invoices, &state)?;
+    Ok(invoices)
+}
+
+// 在Rust端读取PDF文件并解析，不依赖前端 pdf.js
+#[tauri::command]
+async fn parse_invoice_file(
+    path: &str,
+    state: State<'_, AppState>,
+) -> Result<Vec<Invoice>, String> {
+    let mut invoices = parse_file(Path::new(path))?;
+    check_buyers(&mut invoices, &state)?;
+    Ok(invoices)
+}
+
+// 在Rust端并行解析多个PDF文件，通过 batch-progress 事件报告每个文件的进度
+// 解析作为可取消的任务运行，取消后已解析的发票仍保存在当前发票数据中
+#[tauri::command]
+async fn parse_invoice_files(
+    app: AppHandle,
+    paths: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<Invoice>, String> {
+    let paths: Vec<PathBuf> = paths.into_iter().map(PathBuf::from).collect();
+    let job = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?
+        .jobs
+        .create(jobs::JobKind::Parse, paths.len());
+    emit_job_update(&app, &job);
+
+    // 在阻塞线程池中解析，避免占用异步运行时导致界面无响应
+    let app_state = state.inner().clone();
+    let progress_app = app.clone();
+    let job_handle = job.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        let mut invoices = batch::parse_files(&paths, &job_handle, |progress| {
+            if let Err(e) = progress_app.emit(batch::BATCH_PROGRESS_EVENT, progress) {
+                log::warn!("发送解析进度失败: {}", e);
+            }
+        });
+
+        let mut processing_state = app_state
+            .lock()
+            .map_err(|_| "Failed to lock state".to_string())?;
+        buyer_whitelist::apply_buyer_whitelist(&mut invoices, &processing_state.buyer_whitelist);
+        store_invoices(&mut processing_state, invoices.clone())?;
+        Ok(invoices)
+    })
+    .await
+    .map_err(|e| format!("批量解析失败: {}", e))
+    .and_then(|result| result);
+
+    job.finish(result.as_ref().err().map(String::as_str));
+    emit_job_update(&app, &job);
+    result
+}
+
+// 使用当前的抬头白名单核对购买方
+fn check_buyers(invoices: &mut [Invoice], state: &State<'_, AppState>) -> Result<(), String> {
+    let processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+    buyer_whitelist::apply_buyer_whitelist(invoices, &processing_state.buyer_whitelist);
+    Ok(())
+}
+
+// 获取本公司抬头白名单
+#[tauri::command]
+fn get_buyer_whitelist(
+    state: State<AppState>,
+) -> Result<Vec<buyer_whitelist::BuyerEntity>, String> {
+    let processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+    Ok(processing_state.buyer_whitelist.clone())
+}
+
+// 保存本公司抬头白名单到应用配置目录
+#[tauri::command]
+fn set_buyer_whitelist(
+    app: AppHandle,
+    entities: Vec<buyer_whitelist::BuyerEntity>,
+    state: State<AppState>,
+) -> Result<(), String> {
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("获取配置目录失败: {}", e))?;
+    buyer_whitelist::save_whitelist(&buyer_whitelist::whitelist_path(&config_dir), &entities)?;
+
+    let mut processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+    processing_state.buyer_whitelist = entities;
+    Ok(())
+}
+
+// 获取当前使用的发票版式模板，按匹配顺序排列
+#[tauri::command]
+fn get_layout_templates() -> Vec<Arc<layout::LayoutTemplate>> {
+    layout::templates()
+}
+
+// 重新读取版式模板的结果，errors 为被跳过的模板文件的错误
+#[derive(Debug, Serialize)]
+struct LayoutReload {
+    templates: Vec<Arc<layout::LayoutTemplate>>,
+    errors: Vec<String>,
+}
+
+// 重新读取应用配置目录中的版式模板，新增或修改模板文件后无需重启应用
+#[tauri::command]
+fn reload_layout_templates(app: AppHandle) -> Result<LayoutReload, String> {
+    let config_dir = app
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("获取配置目录失败: {}", e))?;
+    let (templates, errors) = layout::load_templates(&layout::layout_dir(&config_dir));
+    layout::set_user_templates(templates);
+    Ok(LayoutReload {
+        templates: layout::templates(),
+        errors,
+    })
+}
+
+async fn update(app: tauri::AppHandle) -> tauri_plugin_updater::Result<()> {
+    if let Some(update) = app.updater()?.check().await? {
+        let mut downloaded = 0;
+
+        // 另外，我们也可以单独调用update.download（）和update.install（）
+        update
+            .download_and_install(
+                |chunk_length, content_length| {
+                    downloaded += chunk_length;
+                    log::debug!("downloaded {downloaded} from {content_length:?}");
+                },
+                || {
+                    log::info!("download finished");
+                },
+            )
+            .await?;
+
+        log::info!("update installed");
+        app.restart();
+    }
+
+    Ok(())
+}
+
+// 选择输出路径
+#[tauri::command]
+async fn select_output_path(app: tauri::AppHandle) -> Result<String, String> {
+    // 只允许选择文件夹
+    let desktop = dirs::desktop_dir().ok_or("无法找到桌面目录")?;
+    let selected_path = app
+        .dialog()
+        .file()
+        .set_directory(desktop)
+        .set_title("选择文件目录")
+        .blocking_pick_folder()
+        .ok_or("已取消选择目录")?;
+    Ok(selected_path.to_string())
+}
+
+// 导出结果，导出作为可取消的任务运行
+#[tauri::command]
+async fn export_results(
+    app: AppHandle,
+    path: String,
+    filename: Option<String>,
+    export_with_details: Option<bool>,
+    export_fields: Option<Vec<String>>, // 添加导出字段参数
+    state: State<'_, AppState>,
+) -> Result<(), String> {
+    let (invoices, job) = {
+        let mut processing_state = state
+            .lock()
+            .map_err(|_| "Failed to lock state".to_string())?;
+
+        if processing_state.invoices.is_empty() {
+            return Err("没有可导出的发票数据".to_string());
+        }
+
+        let invoices = processing_state.invoices.clone();
+        let job = processing_state
+            .jobs
+            .create(jobs::JobKind::Export, invoices.len());
+        (invoices, job)
+    };
+    emit_job_update(&app, &job);
+
+    // 使用提供的文件名或默认名称
+    let file_name = filename.unwrap_or_else(|| "发票数据汇总".to_string());
+    let include_details = export_with_details.unwrap_or(false);
+
+    // 获取要导出的字段列表，如果未提供则使用默认值
+    let fields_to_export = export_fields.unwrap_or_else(default_export_fields);
+
+    let job_handle = job.clone();
+    let result = tauri::async_runtime::spawn_blocking(move || {
+        write_workbook(
+            &invoices,
+            Path::new(&path),
+            &file_name,
+            include_details,
+            &fields_to_export,
+            Some(&job_handle),
+        )
+    })
+    .await
+    .map_err(|e| format!("导出失败: {}", e))
+    .and_then(|result| result);
+
+    job.finish(result.as_ref().err().map(String::as_str));
+    emit_job_update(&app, &job);
+    let excel_path = result?;
+
+    // 打开Excel文件
+    app.opener()
+        .open_path(excel_path.to_str().unwrap(), Option::<String>::None)
+        .map_err(|e| format!("打开Excel文件失败: {}", e))?;
+
+    Ok(())
+}
+
+// 发送任务状态变化事件
+fn emit_job_update(app: &AppHandle, job: &jobs::Job) {
+    if let Err(e) = app.emit(jobs::JOB_EVENT, job.info()) {
+        log::warn!("发送任务状态失败: {}", e);
+    }
+}
+
+// 查询任务状态
+#[tauri::command]
+fn get_job(job_id: u64, state: State<AppState>) -> Result<jobs::JobInfo, String> {
+    let processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+    processing_state
+        .jobs
+        .get(job_id)
+        .map(|job| job.info())
+        .ok_or_else(|| format!("任务不存在: {}", job_id))
+}
+
+// 列出所有任务
+#[tauri::command]
+fn list_jobs(state: State<AppState>) -> Result<Vec<jobs::JobInfo>, String> {
+    let processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+    Ok(processing_state.jobs.list())
+}
+
+// 取消任务，已结束的任务不受影响
+#[tauri::command]
+fn cancel_job(
+    app: AppHandle,
+    job_id: u64,
+    state: State<AppState>,
+) -> Result<jobs::JobInfo, String> {
+    let job = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?
+        .jobs
+        .get(job_id)
+        .ok_or_else(|| format!("任务不存在: {}", job_id))?;
+    if job.cancel() {
+        emit_job_update(&app, &job);
+    }
+    Ok(job.info())
+}
+
+#[tauri::command]
+fn set_invoices(invoices: Vec<Invoice>, state: State<AppState>) -> Result<(), String> {
+    let mut processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+    store_invoices(&mut processing_state, invoices)?;
+    Ok(())
+}
+
+// 重新检查重复、更新统计状态，并保存为当前发票数据
+fn store_invoices(
+    processing_state: &mut ProcessingState,
+    mut invoices: Vec<Invoice>,
+) -> Result<(), String> {
+    processing_state.duplicate_groups = duplicate::apply_duplicate_detection(
+        &mut invoices,
+        processing_state.ledger.as_ref(),
+        &processing_state.batch,
+    )?;
+    processing_state.stats = compute_stats(&invoices);
+    processing_state.invoices = invoices;
+    Ok(())
+}
+
+// 重复检查结果
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DuplicateDetection {
+    invoices: Vec<Invoice>,
+    groups: Vec<duplicate::DuplicateGroup>,
+    stats: ProcessingStats,
+}
+
+// 检查重复发票，设置序号、状态和重复组，并保存为当前发票数据
+#[tauri::command]
+fn detect_duplicates(
+    invoices: Vec<Invoice>,
+    state: State<AppState>,
+) -> Result<DuplicateDetection, String> {
+    let mut processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+    store_invoices(&mut processing_state, invoices)?;
+
+    Ok(DuplicateDetection {
+        invoices: processing_state.invoices.clone(),
+        groups: processing_state.duplicate_groups.clone(),
+        stats: processing_state.stats.clone(),
+    })
+}
+
+// 人工修正发票（item 为 None 时）或明细行的一个字段，记录原值、新值、时间和修正人
+// source 为用户在PDF预览中重新框选的区域，未提供时修正后的字段不再保留原来源区域
+// 修正后重新校验、核对抬头并检查重复，返回更新后的发票数据
+#[tauri::command]
+fn correct_invoice_field(
+    index: usize,
+    item: Option<usize>,
+    field: String,
+    value: String,
+    user: Option<String>,
+    source: Option<provenance::SourceBox>,
+    state: State<AppState>,
+) -> Result<DuplicateDetection, String> {
+    let mut processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+
+    let mut invoices = processing_state.invoices.clone();
+    let invoice = invoices
+        .iter_mut()
+        .find(|invoice| invoice.index == index)
+        .ok_or_else(|| format!("发票不存在: {}", index))?;
+    let user = user
+        .filter(|user| !user.trim().is_empty())
+        .unwrap_or_else(corrections::current_user);
+    let changed = corrections::apply_correction(invoice, item, &field, &value, &user)?;
+    let path = corrections::field_path(item, &field);
+    match source {
+        Some(source) => {
+            invoice
+                .sources
+                .insert(path, provenance::FieldSource::manual(source));
+        }
+        None if changed => {
+            invoice.sources.remove(&path);
+        }
+        None => {}
+    }
+    check_invoice(invoice);
+    buyer_whitelist::apply_buyer_whitelist(
+        std::slice::from_mut(invoice),
+        &processing_state.buyer_whitelist,
+    );
+    store_invoices(&mut processing_state, invoices)?;
+
+    Ok(DuplicateDetection {
+        invoices: processing_state.invoices.clone(),
+        groups: processing_state.duplicate_groups.clone(),
+        stats: processing_state.stats.clone(),
+    })
+}
+
+// 获取人工修正日志
+#[tauri::command]
+fn list_corrections(state: State<AppState>) -> Result<Vec<corrections::CorrectionEntry>, String> {
+    let processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+    Ok(corrections::collect_corrections(&processing_state.invoices))
+}
+
+// 开始新的导入批次，返回批次号。同一批次内的发票不会与台账中本批次的记录比较
+#[tauri::command]
+fn start_import_batch(state: State<AppState>) -> Result<String, String> {
+    let mut processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+    processing_state.batch = ledger::new_batch();
+    Ok(processing_state.batch.clone())
+}
+
+// 将当前正常状态的发票记入台账，返回新增记录数
+#[tauri::command]
+fn record_to_ledger(state: State<AppState>) -> Result<usize, String> {
+    let mut processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+    let processing_state = &mut *processing_state;
+    let ledger = processing_state
+        .ledger
+        .as_mut()
+        .ok_or("发票台账未打开".to_string())?;
+    ledger.record(&processing_state.invoices, &processing_state.batch)
+}
+
+// 查询发票台账
+#[tauri::command]
+fn search_ledger(
+    query: ledger::LedgerQuery,
+    state: State<AppState>,
+) -> Result<Vec<ledger::LedgerEntry>, String> {
+    let processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+    let ledger = processing_state
+        .ledger
+        .as_ref()
+        .ok_or("发票台账未打开".to_string())?;
+    ledger.search(&query)
+}
+
+// 删除台账记录，返回删除的记录数
+#[tauri::command]
+fn remove_ledger_entries(ids: Vec<i64>, state: State<AppState>) -> Result<usize, String> {
+    let processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+    let ledger = processing_state
+        .ledger
+        .as_ref()
+        .ok_or("发票台账未打开".to_string())?;
+    ledger.remove(&ids)
+}
+
+// 标记台账记录已报销，date 为空时取消报销标记，返回更新的记录数
+#[tauri::command]
+fn mark_reimbursed(
+    ids: Vec<i64>,
+    date: Option<String>,
+    state: State<AppState>,
+) -> Result<usize, String> {
+    let processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+    let ledger = processing_state
+        .ledger
+        .as_ref()
+        .ok_or("发票台账未打开".to_string())?;
+    ledger.mark_reimbursed(&ids, date.as_deref())
+}
+
+// 获取当前发票的统计信息
+#[tauri::command]
+fn get_stats(state: State<AppState>) -> Result<ProcessingStats, String> {
+    let processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+    Ok(processing_state.stats.clone())
+}
+
+// 按条件筛选、排序并分页查询当前发票
+#[tauri::command]
+fn list_invoices(
+    query: query::InvoiceQuery,
+    state: State<AppState>,
+) -> Result<query::InvoicePage, String> {
+    let processing_state = state
+        .lock()
+        .map_err(|_| "Failed to lock state".to_string())?;
+    Ok(query::query_invoices(&processing_state.invoices, &query))
+}
+
+// 读取文件内容返回字节数组
+#[tauri::command]
+fn read_file_to_bytes(path: &str) -> Result<Vec<u8>, String> {
+    let bytes = fs::read(path).map_err(|e| format!("无法读取文件: {}", e))?;
+    Ok(bytes)
+}
+
+// 打开PDF文件
+#[tauri::command]
+async fn open_pdf_file(path: &str) -> Result<(), String> {
+    APP.get()
+        .unwrap()
+        .opener()
+        .open_path(path, Option::<String>::None)
+        .map_err(|e| format!("打开PDF文件失败: {}", e))
+}
+
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    // 日志级别由 RUST_LOG 环境变量控制
+    let _ = env_logger::try_init();
+    let processing_state: AppState = Arc::new(Mutex::new(ProcessingState::default()));
+
+    tauri::Builder::default()
+        .plugin(tauri_plugin_updater::Builder::new().build())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_opener::init())
+        .setup(|app| {
+            // 在应用启动时初始化 APP
+            APP.set(app.handle().clone()).unwrap();
+
+            // 读取本公司抬头白名单
+            let config_dir = app.path().app_config_dir()?;
+            let whitelist =
+                buyer_whitelist::load_whitelist(&buyer_whitelist::whitelist_path(&config_dir))
+                    .unwrap_or_else(|e| {
+                        log::error!("{}", e);
+                        Vec::new()
+                    });
+            // 读取用户版式模板，有错误的模板文件被跳过
+            let (templates, errors) = layout::load_templates(&layout::layout_dir(&config_dir));
+            for e in errors {
+                log::error!("{}", e);
+            }
+            layout::set_user_templates(templates);
+            // 打开发票台账
+            let data_dir = app.path().app_data_dir()?;
+            let ledger = ledger::Ledger::open(&ledger::ledger_path(&data_dir))
+                .map_err(|e| log::error!("{}", e))
+                .ok();
+            if let Ok(mut processing_state) = app.state::<AppState>().lock() {
+                processing_state.buyer_whitelist = whitelist;
+                processing_state.ledger = ledger;
+            }
+
+            let handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                update(handle).await.unwrap();
+            });
+            Ok(())
+        })
+        .manage(processing_state)
+        .invoke_handler(tauri::generate_handler![
+            select_output_path,
+            export_results,
+            set_invoices,
+            detect_duplicates,
+            correct_invoice_field,
+            list_corrections,
+            get_stats,
+            list_invoices,
+            start_import_batch,
+            record_to_ledger,
+            search_ledger,
+            remove_ledger_entries,
+            mark_reimbursed,
+            get_job,
+            list_jobs,
+            cancel_job,
+            read_file_to_bytes,
+            parse_invoice_text,
+            parse_invoice_file,
+            parse_invoice_files,
+            get_buyer_whitelist,
+            set_buyer_whitelist,
+            get_layout_templates,
+            reload_layout_templates,
+            open_pdf_file
+        ])
+        .run(tauri::generate_context!())
+        .expect("error while running tauri application");
+}
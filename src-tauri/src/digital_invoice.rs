@@ -0,0 +1,268 @@
+use crate::{
+    extract_invoice_items, extract_labeled_value_with_source, extract_remark_info,
+    extract_total_amount_and_tax, is_special_invoice_type, layout, provenance, Invoice, TextItem,
+};
+
+// 全电发票（全面数字化电子发票）的发票类型
+pub(crate) const FULLY_DIGITAL_NORMAL_TYPE: &str = "电子发票（普通发票）";
+pub(crate) const FULLY_DIGITAL_SPECIAL_TYPE: &str = "电子发票（增值税专用发票）";
+
+// 判断是否为全电发票版式：没有发票代码，标题为"电子发票（…）"或发票号码为20位
+pub(crate) fn is_fully_digital(text_items: &[TextItem]) -> bool {
+    if text_items.iter().any(|item| item.text.contains("发票代码")) {
+        return false;
+    }
+
+    let title_regex = regex::Regex::new(r"^电[⼦子]发票([（(]|$)").unwrap();
+    let number_regex = regex::Regex::new(r"^(发票号码[:：]?)?\d{20}$").unwrap();
+    text_items
+        .iter()
+        .any(|item| title_regex.is_match(&item.text) || number_regex.is_match(&item.text))
+}
+
+// 提取全电发票标题，兼容 pdf.js 将"电子发票"与"（普通发票）"拆分为两个文本项的情况
+fn extract_title(text_items: &[TextItem]) -> String {
+    let title_regex = regex::Regex::new(r"^电[⼦子]发票").unwrap();
+    let suffix_regex = regex::Regex::new(r"^[（(].*发票[)）]$").unwrap();
+
+    let title_item = match text_items
+        .iter()
+        .find(|item| title_regex.is_match(&item.text))
+    {
+        Some(item) => item,
+        None => return "".to_string(),
+    };
+
+    if title_item.text.contains('（') || title_item.text.contains('(') {
+        return title_item.text.clone();
+    }
+
+    let suffix = text_items
+        .iter()
+        .filter(|item| {
+            suffix_regex.is_match(&item.text)
+                && (item.y - title_item.y).abs() < 10.0
+                && item.x > title_item.x
+        })
+        .min_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
+
+    match suffix {
+        Some(suffix) => format!("{}{}", title_item.text, suffix.text),
+        None => title_item.text.clone(),
+    }
+}
+
+// 提取全电发票购买方、销售方信息，两者左右并列，以竖排的"购买方信息"/"销售方信息"为界
+fn extract_party_info(
+    text_items: &[TextItem],
+    page_text: &str,
+    invoice: &mut Invoice,
+    is_seller_info: bool,
+) {
+    // 区域偏移量和字段标签取自版式模板
+    let template = match layout::party_columns(&invoice.invoice_type, page_text) {
+        Some(template) => template,
+        None => return,
+    };
+    let (layout, regexes) = match template
+        .party_columns()
+        .and_then(|layout| Some((layout, layout.regexes()?)))
+    {
+        Some(layout) => layout,
+        None => return,
+    };
+
+    let find_header = |is_seller_info: bool| {
+        let anchor = regexes.anchor(is_seller_info);
+        text_items.iter().find(|item| anchor.is_match(&item.text))
+    };
+
+    let buyer_header = find_header(false);
+    let seller_header = find_header(true);
+    let header_item = if is_seller_info {
+        seller_header
+    } else {
+        buyer_header
+    };
+    let header_item = match header_item {
+        Some(item) => item,
+        None => return,
+    };
+
+    // 竖排标题的最后一个字"息"作为区域下边界
+    let footer_y = text_items
+        .iter()
+        .filter(|item| {
+            item.text == "息" && (item.x - header_item.x).abs() <= 2.0 && item.y > header_item.y
+        })
+        .map(|item| item.y)
+        .min_by(|a, b| a.partial_cmp(b).unwrap())
+        .unwrap_or(header_item.y + layout.fallback_height);
+
+    // 购买方区域右边界为销售方标题，销售方区域延伸到页面右侧
+    let area_left = header_item.x + header_item.width + layout.left;
+    let area_right = if is_seller_info {
+        f64::MAX
+    } else {
+        seller_header.map(|item| item.x).unwrap_or(f64::MAX)
+    };
+    let area_top = header_item.y - header_item.height.max(layout.top);
+    let area_bottom = footer_y + layout.bottom;
+
+    let area_items: Vec<&TextItem> = text_items
+        .iter()
+        .filter(|item| {
+            item.x >= area_left
+                && item.x < area_right
+                && item.y >= area_top
+                && item.y <= area_bottom
+                && item.page_index == header_item.page_index
+        })
+        .collect();
+
+    // 获取特定字段的值及其所在的文本项：标签与值可能在同一个文本项中
+    let get_field_value = |label_pattern: &regex::Regex| -> provenance::Extracted {
+        let label_item = match area_items
+            .iter()
+            .find(|item| label_pattern.is_match(&item.text))
+        {
+            Some(item) => *item,
+            None => return provenance::Extracted::empty(),
+        };
+
+        let inline_value = label_pattern
+            .replace(&label_item.text, "")
+            .trim()
+            .to_string();
+        if !inline_value.is_empty() {
+            return provenance::Extracted {
+                value: inline_value,
+                items: vec![label_item],
+                label_score: 1.0,
+            };
+        }
+
+        let label_right = label_item.x + label_item.width;
+        let mut value_items: Vec<&TextItem> = area_items
+            .iter()
+            .filter(|item| {
+                item.x + item.width > label_right
+                    && (item.y - label_item.y).abs() <= layout.row_tolerance
+                    && !item.text.contains(':')
+                    && !item.text.contains('：')
+            })
+            .copied()
+            .collect();
+        value_items.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
+
+        let value = value_items
+            .iter()
+            .map(|item| item.text.as_str())
+            .collect::<String>();
+        provenance::Extracted {
+            value,
+            label_score: provenance::label_score(
+                label_item,
+                label_pattern,
+                &value_items,
+                layout.label_distance,
+            ),
+            items: value_items,
+        }
+    };
+
+    let (party_obj, prefix) = if is_seller_info {
+        (&mut invoice.seller, "seller")
+    } else {
+        (&mut invoice.buyer, "buyer")
+    };
+    let sources = &mut invoice.sources;
+
+    // 提取名称
+    party_obj.name = provenance::record_value(
+        sources,
+        &format!("{}.name", prefix),
+        get_field_value(&regexes.name),
+    );
+
+    // 提取统一社会信用代码/纳税人识别号
+    party_obj.tax_code = provenance::record_value(
+        sources,
+        &format!("{}.tax_code", prefix),
+        get_field_value(&regexes.tax_code),
+    );
+}
+
+// 解析全电发票
+pub(crate) fn parse_fully_digital_fapiao(
+    text_items: &[TextItem],
+    page_text: &str,
+    mut invoice: Invoice,
+    page_index: usize,
+) -> Invoice {
+    // 设置页面索引
+    invoice.index = page_index + 1;
+
+    // 提取标题
+    let title = extract_title(text_items);
+    let title = if title.is_empty() {
+        FULLY_DIGITAL_NORMAL_TYPE.to_string()
+    } else {
+        title
+    };
+    invoice.invoice_type = if title.contains("专用发票") {
+        FULLY_DIGITAL_SPECIAL_TYPE.to_string()
+    } else {
+        FULLY_DIGITAL_NORMAL_TYPE.to_string()
+    };
+    invoice.title = if page_index == 0 {
+        title
+    } else {
+        format!("{} (第{}页)", title, page_index + 1)
+    };
+
+    // 专用发票的进项税额可抵扣
+    invoice.deductible = is_special_invoice_type(&invoice.invoice_type);
+
+    // 提取发票号码，全电发票没有发票代码和校验码
+    let number_regex = regex::Regex::new(r"发票号码[:：]?").unwrap();
+    invoice.number = provenance::record_value(
+        &mut invoice.sources,
+        "number",
+        extract_labeled_value_with_source(text_items, &number_regex, 150.0)
+            .map_value(|number| number.chars().filter(|c| c.is_ascii_digit()).collect()),
+    );
+
+    // 提取开票日期
+    let date_regex = regex::Regex::new(r"开票日期[:：]?").unwrap();
+    invoice.date = provenance::record_value(
+        &mut invoice.sources,
+        "date",
+        extract_labeled_value_with_source(text_items, &date_regex, 150.0),
+    );
+
+    // 提取购买方信息
+    extract_party_info(text_items, page_text, &mut invoice, false);
+
+    // 提取销售方信息
+    extract_party_info(text_items, page_text, &mut invoice, true);
+
+    // 提取开票人
+    let drawer_regex = regex::Regex::new(r"^开票.{0,1}[:：]").unwrap();
+    invoice.drawer = provenance::record_value(
+        &mut invoice.sources,
+        "drawer",
+        extract_labeled_value_with_source(text_items, &drawer_regex, 100.0),
+    );
+
+    // 提取备注
+    extract_remark_info(text_items, page_text, &mut invoice);
+
+    // 提取商品信息
+    extract_invoice_items(text_items, &mut invoice);
+
+    // 提取合计金额和合计税额
+    extract_total_amount_and_tax(text_items, &mut invoice);
+
+    invoice
+}
@@ -0,0 +1,383 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+use crate::{buyer_whitelist, dates, ledger::Ledger, red_letter, validation, Invoice};
+
+pub(crate) const DUPLICATE_STATUS: &str = "重复";
+pub(crate) const SUSPECTED_DUPLICATE_STATUS: &str = "疑似重复";
+
+// 一组重复发票，indexes 中第一个为最先出现的发票序号
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DuplicateGroup {
+    group: usize,
+    reason: String,
+    indexes: Vec<usize>,
+}
+
+// 计算文件内容的 SHA-256，用于识别内容完全相同的文件
+pub(crate) fn file_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// 发票号码键：全电发票没有发票代码，仅按发票号码比较
+pub(crate) fn number_key(invoice: &Invoice) -> Option<String> {
+    if invoice.number.is_empty()
+        || invoice.number.starts_with("文件：")
+        || invoice.code == "未能解析"
+    {
+        return None;
+    }
+    Some(format!("{}-{}", invoice.code, invoice.number))
+}
+
+// 文件内容键：同一内容文件的同一页
+fn file_key(invoice: &Invoice) -> Option<String> {
+    if invoice.file_hash.is_empty() {
+        return None;
+    }
+    Some(format!("{}#{}", invoice.file_hash, invoice.page))
+}
+
+// 疑似重复的相似度阈值，及参与比较的字段至少占总权重的比例
+const SIMILARITY_THRESHOLD: f64 = 0.85;
+const MIN_COMPARED_WEIGHT: f64 = 0.7;
+// 各字段权重：销售方税号、开票日期、价税合计、商品名称
+const SELLER_WEIGHT: f64 = 0.3;
+const DATE_WEIGHT: f64 = 0.2;
+const AMOUNT_WEIGHT: f64 = 0.3;
+const ITEMS_WEIGHT: f64 = 0.2;
+
+// 疑似重复比较所用的发票特征
+struct Fingerprint {
+    seller_tax_code: String,
+    // 开票日期，如 20240506，无法识别时为空
+    date: String,
+    amount: Option<i64>,
+    item_names: String,
+}
+
+impl Fingerprint {
+    fn new(invoice: &Invoice) -> Self {
+        let mut item_names: Vec<String> = invoice
+            .items
+            .iter()
+            .map(|item| item.name.chars().filter(|c| !c.is_whitespace()).collect())
+            .filter(|name: &String| !name.is_empty())
+            .collect();
+        item_names.sort();
+
+        Fingerprint {
+            seller_tax_code: invoice
+                .seller
+                .tax_code
+                .chars()
+                .filter(|c| !c.is_whitespace())
+                .collect::<String>()
+                .to_uppercase(),
+            date: dates::invoice_date(invoice)
+                .map(|date| date.format("%Y%m%d").to_string())
+                .unwrap_or_default(),
+            amount: invoice.total_amount_tax.cents(),
+            item_names: item_names.join("|"),
+        }
+    }
+
+    // 相似度，价税合计必须一致；双方均为空的字段不参与比较
+    fn similarity(&self, other: &Fingerprint) -> Option<f64> {
+        if self.amount.is_none() || self.amount != other.amount {
+            return None;
+        }
+
+        let exact = |a: &str, b: &str| {
+            if a.is_empty() && b.is_empty() {
+                None
+            } else if a == b {
+                Some(1.0)
+            } else {
+                Some(0.0)
+            }
+        };
+        let items = if self.item_names.is_empty() && other.item_names.is_empty() {
+            None
+        } else {
+            Some(text_similarity(&self.item_names, &other.item_names))
+        };
+
+        let mut compared_weight = AMOUNT_WEIGHT;
+        let mut score = AMOUNT_WEIGHT;
+        for (weight, similarity) in [
+            (
+                SELLER_WEIGHT,
+                exact(&self.seller_tax_code, &other.seller_tax_code),
+            ),
+            (DATE_WEIGHT, exact(&self.date, &other.date)),
+            (ITEMS_WEIGHT, items),
+        ] {
+            if let Some(similarity) = similarity {
+                compared_weight += weight;
+                score += weight * similarity;
+            }
+        }
+
+        if compared_weight < MIN_COMPARED_WEIGHT {
+            return None;
+        }
+        Some(score / compared_weight)
+    }
+}
+
+// 文本相似度，使用相邻字符对的 Dice 系数
+fn text_similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let bigrams = |text: &str| -> Vec<(char, char)> {
+        let chars: Vec<char> = text.chars().collect();
+        let mut pairs: Vec<(char, char)> = chars.windows(2).map(|w| (w[0], w[1])).collect();
+        pairs.sort();
+        pairs
+    };
+    let a_pairs = bigrams(a);
+    let mut b_pairs = bigrams(b);
+    if a_pairs.is_empty() || b_pairs.is_empty() {
+        return 0.0;
+    }
+
+    let total = a_pairs.len() + b_pairs.len();
+    let mut common = 0;
+    for pair in &a_pairs {
+        if let Some(pos) = b_pairs.iter().position(|other| other == pair) {
+            b_pairs.swap_remove(pos);
+            common += 1;
+        }
+    }
+    2.0 * common as f64 / total as f64
+}
+
+// 将发票加入以 first_index 为首的重复组，返回重复组编号
+fn join_group(
+    groups: &mut Vec<DuplicateGroup>,
+    group_of_first: &mut HashMap<(usize, &'static str), usize>,
+    first_index: usize,
+    index: usize,
+    reason: &'static str,
+) -> usize {
+    let group_position = *group_of_first
+        .entry((first_index, reason))
+        .or_insert_with(|| {
+            groups.push(DuplicateGroup {
+                group: groups.len() + 1,
+                reason: reason.to_string(),
+                indexes: vec![first_index],
+            });
+            groups.len() - 1
+        });
+    groups[group_position].indexes.push(index);
+    groups[group_position].group
+}
+
+// 检查重复并设置序号、状态和重复组，返回所有重复组
+// 提供台账时，本批次内不重复的发票再与台账中其他批次的记录比较
+// 发票号码和文件内容均不同，但销售方、日期、金额和商品相似的发票标记为疑似重复
+// 最后关联红字发票与其冲红的正数发票
+pub(crate) fn apply_duplicate_detection(
+    invoices: &mut [Invoice],
+    ledger: Option<&Ledger>,
+    batch: &str,
+) -> Result<Vec<DuplicateGroup>, String> {
+    let mut seen_numbers: HashMap<String, usize> = HashMap::new();
+    let mut seen_files: HashMap<String, usize> = HashMap::new();
+    let mut groups: Vec<DuplicateGroup> = Vec::new();
+    // (最先出现的发票序号, 原因) -> 重复组下标
+    let mut group_of_first: HashMap<(usize, &'static str), usize> = HashMap::new();
+    let fingerprints: Vec<Fingerprint> = invoices.iter().map(Fingerprint::new).collect();
+    // 价税合计 -> 可作为疑似重复比较对象的发票下标
+    let mut candidates: HashMap<i64, Vec<usize>> = HashMap::new();
+
+    for i in 0..invoices.len() {
+        let invoice = &mut invoices[i];
+        // 设置序号
+        invoice.index = i + 1;
+        invoice.duplicate_group = 0;
+
+        if invoice.status == "解析失败" {
+            // 如果是解析失败状态，保持该状态
+            continue;
+        }
+        invoice.duplicate_info = "".to_string();
+
+        let number_key = number_key(invoice);
+        let file_key = file_key(invoice);
+
+        // 优先按发票号码判断，其次按文件内容判断
+        let duplicate_of = number_key
+            .as_ref()
+            .and_then(|key| seen_numbers.get(key))
+            .map(|&first| (first, "发票号码相同"))
+            .or_else(|| {
+                file_key
+                    .as_ref()
+                    .and_then(|key| seen_files.get(key))
+                    .map(|&first| (first, "文件内容相同"))
+            });
+
+        let (first_index, reason) = match duplicate_of {
+            Some((first_index, reason)) => {
+                invoice.status = DUPLICATE_STATUS.to_string();
+                invoice.duplicate_info = if reason == "文件内容相同" {
+                    format!("与第{}个发票文件内容相同", first_index)
+                } else {
+                    format!("与第{}个发票重复", first_index)
+                };
+                (first_index, reason)
+            }
+            None => {
+                if let Some(key) = number_key {
+                    seen_numbers.insert(key, invoice.index);
+                }
+                if let Some(key) = file_key {
+                    seen_files.insert(key, invoice.index);
+                }
+
+                // 之前批次已记入台账的发票
+                if let Some(entry) = match ledger {
+                    Some(ledger) => ledger.find_duplicate(invoice, batch)?,
+                    None => None,
+                } {
+                    invoice.status = DUPLICATE_STATUS.to_string();
+                    invoice.duplicate_info = entry.duplicate_info();
+                    continue;
+                }
+
+                // 与本批次之前的发票比较相似度，取最相似的一张
+                let fingerprint = &fingerprints[i];
+                let similar = fingerprint
+                    .amount
+                    .and_then(|amount| candidates.get(&amount))
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|&j| {
+                        fingerprint
+                            .similarity(&fingerprints[j])
+                            .map(|score| (j, score))
+                    })
+                    .filter(|&(_, score)| score >= SIMILARITY_THRESHOLD)
+                    .max_by(|a, b| a.1.total_cmp(&b.1));
+
+                match similar {
+                    Some((j, score)) => {
+                        invoice.status = SUSPECTED_DUPLICATE_STATUS.to_string();
+                        invoice.duplicate_info =
+                            format!("与第{}个发票疑似重复（相似度{:.0}%）", j + 1, score * 100.0);
+                        (j + 1, "疑似重复")
+                    }
+                    None => {
+                        invoice.status = if !invoice.buyer_mismatch.is_empty() {
+                            buyer_whitelist::BUYER_MISMATCH_STATUS.to_string()
+                        } else if validation::needs_review(&invoice.validation) {
+                            validation::REVIEW_STATUS.to_string()
+                        } else {
+                            "正常".to_string()
+                        };
+                        if let Some(amount) = fingerprint.amount {
+                            candidates.entry(amount).or_default().push(i);
+                        }
+                        continue;
+                    }
+                }
+            }
+        };
+
+        let group = join_group(&mut groups, &mut group_of_first, first_index, i + 1, reason);
+        invoices[i].duplicate_group = group;
+        // 最先出现的发票同时属于多个重复组时，保留先建立的组
+        if invoices[first_index - 1].duplicate_group == 0 {
+            invoices[first_index - 1].duplicate_group = group;
+        }
+    }
+
+    red_letter::link_originals(invoices, ledger)?;
+    Ok(groups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fingerprint(seller: &str, date: &str, amount: Option<i64>, items: &str) -> Fingerprint {
+        Fingerprint {
+            seller_tax_code: seller.to_string(),
+            date: date.to_string(),
+            amount,
+            item_names: items.to_string(),
+        }
+    }
+
+    const SELLER: &str = "91350100M000100Y43";
+
+    #[test]
+    fn exact_duplicate() {
+        let a = fingerprint(SELLER, "20240506", Some(10600), "*餐饮服务*餐费");
+        let b = fingerprint(SELLER, "20240506", Some(10600), "*餐饮服务*餐费");
+        assert_eq!(a.similarity(&b), Some(1.0));
+    }
+
+    #[test]
+    fn amount_must_match() {
+        let a = fingerprint(SELLER, "20240506", Some(10600), "*餐饮服务*餐费");
+        let b = fingerprint(SELLER, "20240506", Some(10601), "*餐饮服务*餐费");
+        assert_eq!(a.similarity(&b), None);
+
+        let missing = fingerprint(SELLER, "20240506", None, "*餐饮服务*餐费");
+        assert_eq!(missing.similarity(&missing), None);
+    }
+
+    #[test]
+    fn fields_missing_on_both_sides_are_skipped() {
+        // 双方都没有销售方税号：金额、日期、商品共 0.7，刚好满足最低比较权重
+        let a = fingerprint("", "20240506", Some(10600), "*餐饮服务*餐费");
+        let b = fingerprint("", "20240506", Some(10600), "*餐饮服务*餐费");
+        assert_eq!(a.similarity(&b), Some(1.0));
+
+        // 再缺少开票日期时参与比较的权重不足，不判断为疑似重复
+        let a = fingerprint("", "", Some(10600), "*餐饮服务*餐费");
+        let b = fingerprint("", "", Some(10600), "*餐饮服务*餐费");
+        assert_eq!(a.similarity(&b), None);
+
+        // 只有一方缺失时按不一致计分
+        let a = fingerprint(SELLER, "20240506", Some(10600), "*餐饮服务*餐费");
+        let b = fingerprint("", "20240506", Some(10600), "*餐饮服务*餐费");
+        let similarity = a.similarity(&b).unwrap();
+        assert!((similarity - 0.7).abs() < 1e-9);
+        assert!(similarity < SIMILARITY_THRESHOLD);
+    }
+
+    #[test]
+    fn similar_item_names() {
+        let similarity = text_similarity("*信息技术服务*软件服务费", "*信息技术服务*技术服务费");
+        assert!((similarity - 0.75).abs() < 1e-9);
+        assert!(similarity < SIMILARITY_THRESHOLD);
+
+        // 商品名称只占 0.2 的权重，其他字段一致时仍为疑似重复
+        let a = fingerprint(SELLER, "20240506", Some(10600), "*信息技术服务*软件服务费");
+        let b = fingerprint(SELLER, "20240506", Some(10600), "*信息技术服务*技术服务费");
+        let similarity = a.similarity(&b).unwrap();
+        assert!((similarity - 0.95).abs() < 1e-9);
+        assert!(similarity >= SIMILARITY_THRESHOLD);
+
+        // 商品名称完全不同时低于阈值
+        let c = fingerprint(SELLER, "20240506", Some(10600), "*餐饮服务*餐费");
+        assert!(a.similarity(&c).unwrap() < SIMILARITY_THRESHOLD);
+    }
+
+    #[test]
+    fn text_similarity_edge_cases() {
+        assert_eq!(text_similarity("", ""), 1.0);
+        assert_eq!(text_similarity("餐", "餐费"), 0.0);
+        assert_eq!(text_similarity("餐费", "住宿"), 0.0);
+    }
+}
@@ -0,0 +1,246 @@
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    Arc, Mutex,
+};
+
+// 任务状态变化事件名称
+pub(crate) const JOB_EVENT: &str = "job-update";
+
+// 保留的已结束任务数量，超出时删除最早结束的任务
+const MAX_FINISHED_JOBS: usize = 50;
+
+// 任务类型
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobKind {
+    Parse,
+    Export,
+}
+
+// 任务状态
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Running,
+    Completed,
+    Cancelled,
+    Failed,
+}
+
+// 任务信息，返回给前端
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JobInfo {
+    id: u64,
+    kind: JobKind,
+    status: JobStatus,
+    // 总工作量与已完成量：解析任务为文件数，导出任务为写入的发票行数
+    total: usize,
+    done: usize,
+    // 失败原因
+    message: String,
+}
+
+// 长时间运行的任务，工作线程通过 is_cancelled 检查是否需要停止
+pub struct Job {
+    id: u64,
+    kind: JobKind,
+    total: usize,
+    done: AtomicUsize,
+    cancel_requested: AtomicBool,
+    outcome: Mutex<(JobStatus, String)>,
+}
+
+impl Job {
+    pub(crate) fn new(id: u64, kind: JobKind, total: usize) -> Self {
+        Job {
+            id,
+            kind,
+            total,
+            done: AtomicUsize::new(0),
+            cancel_requested: AtomicBool::new(false),
+            outcome: Mutex::new((JobStatus::Running, String::new())),
+        }
+    }
+
+    pub(crate) fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub(crate) fn status(&self) -> JobStatus {
+        self.outcome
+            .lock()
+            .map(|outcome| outcome.0)
+            .unwrap_or(JobStatus::Failed)
+    }
+
+    // 完成一项工作
+    pub(crate) fn advance(&self) {
+        self.done.fetch_add(1, Ordering::SeqCst);
+    }
+
+    // 请求取消，任务已结束时返回 false
+    pub(crate) fn cancel(&self) -> bool {
+        if self.status() != JobStatus::Running {
+            return false;
+        }
+        self.cancel_requested.store(true, Ordering::SeqCst);
+        true
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.cancel_requested.load(Ordering::SeqCst)
+    }
+
+    // 已请求取消时返回错误，用于中止导出等操作
+    pub(crate) fn check_cancelled(&self) -> Result<(), String> {
+        if self.is_cancelled() {
+            Err("任务已取消".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    // 结束任务：已请求取消时为已取消，否则根据是否出错为已完成或失败
+    pub(crate) fn finish(&self, error: Option<&str>) {
+        let outcome = if self.is_cancelled() {
+            (JobStatus::Cancelled, String::new())
+        } else {
+            match error {
+                Some(error) => (JobStatus::Failed, error.to_string()),
+                None => (JobStatus::Completed, String::new()),
+            }
+        };
+        if let Ok(mut current) = self.outcome.lock() {
+            *current = outcome;
+        }
+    }
+
+    pub(crate) fn info(&self) -> JobInfo {
+        let (status, message) = self
+            .outcome
+            .lock()
+            .map(|outcome| outcome.clone())
+            .unwrap_or((JobStatus::Failed, String::new()));
+        JobInfo {
+            id: self.id,
+            kind: self.kind,
+            status,
+            total: self.total,
+            done: self.done.load(Ordering::SeqCst),
+            message,
+        }
+    }
+}
+
+// 任务列表
+#[derive(Default)]
+pub struct JobRegistry {
+    next_id: u64,
+    jobs: Vec<Arc<Job>>,
+}
+
+impl JobRegistry {
+    // 创建任务并分配任务ID
+    pub(crate) fn create(&mut self, kind: JobKind, total: usize) -> Arc<Job> {
+        self.next_id += 1;
+        let job = Arc::new(Job::new(self.next_id, kind, total));
+        self.jobs.push(job.clone());
+
+        // 清理过多的已结束任务
+        let finished = self
+            .jobs
+            .iter()
+            .filter(|job| job.status() != JobStatus::Running)
+            .count();
+        if finished > MAX_FINISHED_JOBS {
+            let mut to_remove = finished - MAX_FINISHED_JOBS;
+            self.jobs.retain(|job| {
+                if to_remove > 0 && job.status() != JobStatus::Running {
+                    to_remove -= 1;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+
+        job
+    }
+
+    pub(crate) fn get(&self, id: u64) -> Option<Arc<Job>> {
+        self.jobs.iter().find(|job| job.id == id).cloned()
+    }
+
+    pub(crate) fn list(&self) -> Vec<JobInfo> {
+        self.jobs.iter().map(|job| job.info()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::{self, BatchEventKind};
+    use std::path::PathBuf;
+
+    #[test]
+    fn progress_and_outcome() {
+        let job = Job::new(1, JobKind::Export, 3);
+        job.advance();
+        job.advance();
+        let info = job.info();
+        assert_eq!((info.total, info.done), (3, 2));
+        assert_eq!(info.status, JobStatus::Running);
+
+        job.finish(None);
+        assert_eq!(job.status(), JobStatus::Completed);
+        // 已结束的任务不能取消
+        assert!(!job.cancel());
+        assert!(job.check_cancelled().is_ok());
+
+        let job = Job::new(2, JobKind::Export, 1);
+        job.finish(Some("写入失败"));
+        let info = job.info();
+        assert_eq!(info.status, JobStatus::Failed);
+        assert_eq!(info.message, "写入失败");
+    }
+
+    #[test]
+    fn cancel_stops_remaining_files() {
+        let dir = std::env::temp_dir().join(format!("job_cancel_{}", std::process::id()));
+        let paths: Vec<PathBuf> = (0..5).map(|i| dir.join(format!("{}.pdf", i))).collect();
+        let job = Job::new(1, JobKind::Parse, paths.len());
+
+        // 单线程按顺序解析，第一个文件结束后取消任务
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap();
+        let invoices = pool.install(|| {
+            batch::parse_files(&paths, &job, |progress| {
+                if progress.kind() == BatchEventKind::Failed {
+                    job.cancel();
+                }
+            })
+        });
+
+        assert_eq!(invoices.len(), 1);
+        assert_eq!(job.info().done, 1);
+        assert!(job.check_cancelled().is_err());
+        job.finish(None);
+        assert_eq!(job.status(), JobStatus::Cancelled);
+    }
+
+    #[test]
+    fn registry_keeps_recent_finished_jobs() {
+        let mut registry = JobRegistry::default();
+        for _ in 0..MAX_FINISHED_JOBS + 10 {
+            registry.create(JobKind::Parse, 1).finish(None);
+        }
+        let running = registry.create(JobKind::Export, 1);
+
+        assert_eq!(registry.list().len(), MAX_FINISHED_JOBS + 1);
+        assert!(registry.get(1).is_none());
+        assert!(registry.get(running.id()).is_some());
+    }
+}
@@ -0,0 +1,583 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use crate::{
+    buyer_whitelist::APP_IDENTIFIER, corrections, create_empty_invoice, group_items_by_row,
+    provenance, Invoice, TextItem,
+};
+
+// 用户模板保存在应用配置目录下的该目录中，每个 JSON 文件一个模板
+const LAYOUT_DIR_NAME: &str = "layouts";
+
+// 内置模板，按顺序匹配，不限发票类型的默认版式放在最后
+const BUILTIN_TEMPLATES: [&str; 4] = [
+    include_str!("../layouts/fully_digital.json"),
+    include_str!("../layouts/special.json"),
+    include_str!("../layouts/general.json"),
+    include_str!("../layouts/default.json"),
+];
+
+// 当前使用的模板：用户模板在前，其后为未被同名用户模板覆盖的内置模板
+static TEMPLATES: OnceLock<RwLock<Vec<Arc<LayoutTemplate>>>> = OnceLock::new();
+
+// 发票版式模板：描述锚点、相对锚点的搜索区域和字段正则
+// 匹配发票类型和页面文本的模板中，取第一个定义了所需区域的模板
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LayoutTemplate {
+    name: String,
+    // 适用的发票类型，为空时适用于所有类型
+    #[serde(default)]
+    invoice_types: Vec<String>,
+    // 页面文本（去除空白）需匹配的正则，用于区分同一发票类型的不同省份版式，为空时不限制
+    #[serde(default)]
+    detect: String,
+    // 购买方、销售方信息区域
+    #[serde(default)]
+    party: Option<PartyLayout>,
+    // 左右并列的购买方、销售方信息区域，用于全电发票
+    #[serde(default)]
+    party_columns: Option<PartyColumns>,
+    // 备注区域
+    #[serde(default)]
+    remark: Option<Region>,
+    // 按区域提取的其他字段，提取到值时覆盖解析器识别的结果
+    #[serde(default)]
+    fields: Vec<FieldRule>,
+    // 模板文件路径，内置模板为空
+    #[serde(default, skip_deserializing)]
+    path: String,
+    // 编译后的页面文本正则，detect 为空时为 None
+    #[serde(skip)]
+    detect_regex: Option<regex::Regex>,
+}
+
+// 购买方、销售方信息区域，下边界为竖排标题末字"息"（或"方"）
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PartyLayout {
+    // 竖排标题首字的正则
+    #[serde(default = "default_buyer_anchor")]
+    pub(crate) buyer_anchor: String,
+    #[serde(default = "default_seller_anchor")]
+    pub(crate) seller_anchor: String,
+    // 区域左右边界相对锚点左边界的偏移量
+    pub(crate) left: f64,
+    pub(crate) right: f64,
+    // 区域上下边界相对锚点和标题末字向外扩展的距离
+    #[serde(default)]
+    pub(crate) vertical: f64,
+    // 区域右侧紧邻密码区时，不越过密码区标签
+    #[serde(default)]
+    pub(crate) stop_at_password: bool,
+    #[serde(default)]
+    pub(crate) labels: PartyLabels,
+    // 值与标签视为同一行的最大纵向距离
+    #[serde(default = "default_row_tolerance")]
+    pub(crate) row_tolerance: f64,
+    // 编译后的锚点和标签正则，读取模板时生成
+    #[serde(skip)]
+    regexes: Option<PartyRegexes>,
+}
+
+// 购买方、销售方信息区域的锚点和字段标签正则
+#[derive(Debug, Clone)]
+pub(crate) struct PartyRegexes {
+    buyer_anchor: regex::Regex,
+    seller_anchor: regex::Regex,
+    pub(crate) name: regex::Regex,
+    pub(crate) tax_code: regex::Regex,
+    pub(crate) address_phone: regex::Regex,
+    pub(crate) bank_account: regex::Regex,
+}
+
+// 左右并列的购买方、销售方信息区域（全电发票），锚点为竖排标题，下边界为标题末字"息"
+// 购买方区域的右边界为销售方标题，销售方区域延伸到页面右侧
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PartyColumns {
+    // 购买方、销售方标题的正则
+    pub(crate) buyer_anchor: String,
+    pub(crate) seller_anchor: String,
+    // 区域左边界相对锚点右边界的偏移量
+    #[serde(default)]
+    pub(crate) left: f64,
+    // 区域上边界在锚点上方的最小距离，锚点文本更高时取文本高度
+    pub(crate) top: f64,
+    // 区域下边界在标题末字下方的距离
+    pub(crate) bottom: f64,
+    // 找不到标题末字时，区域下边界在锚点下方的距离
+    pub(crate) fallback_height: f64,
+    // 字段标签的正则，全电发票只提取名称和纳税人识别号
+    #[serde(default)]
+    pub(crate) labels: PartyLabels,
+    // 值与标签视为同一行的最大纵向距离
+    #[serde(default = "default_row_tolerance")]
+    pub(crate) row_tolerance: f64,
+    // 值与标签的参考距离，用于计算标签匹配程度
+    pub(crate) label_distance: f64,
+    // 编译后的锚点和标签正则，读取模板时生成
+    #[serde(skip)]
+    regexes: Option<PartyRegexes>,
+}
+
+// 购买方、销售方各字段标签的正则
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PartyLabels {
+    pub(crate) name: String,
+    pub(crate) tax_code: String,
+    pub(crate) address_phone: String,
+    pub(crate) bank_account: String,
+}
+
+impl Default for PartyLabels {
+    fn default() -> Self {
+        PartyLabels {
+            name: r"称[:：]?$".to_string(),
+            tax_code: r"识别号[:：]?$".to_string(),
+            address_phone: r"电话[:：]?$".to_string(),
+            bank_account: r"开户行及账号[:：]?$".to_string(),
+        }
+    }
+}
+
+fn default_buyer_anchor() -> String {
+    "^购$".to_string()
+}
+
+fn default_seller_anchor() -> String {
+    "^销$".to_string()
+}
+
+fn default_row_tolerance() -> f64 {
+    6.0
+}
+
+impl PartyLayout {
+    pub(crate) fn regexes(&self) -> Option<&PartyRegexes> {
+        self.regexes.as_ref()
+    }
+}
+
+impl PartyColumns {
+    pub(crate) fn regexes(&self) -> Option<&PartyRegexes> {
+        self.regexes.as_ref()
+    }
+}
+
+impl PartyRegexes {
+    fn compile(
+        compile_regex: impl Fn(&str) -> Result<regex::Regex, String>,
+        buyer_anchor: &str,
+        seller_anchor: &str,
+        labels: &PartyLabels,
+    ) -> Result<Self, String> {
+        Ok(PartyRegexes {
+            buyer_anchor: compile_regex(buyer_anchor)?,
+            seller_anchor: compile_regex(seller_anchor)?,
+            name: compile_regex(&labels.name)?,
+            tax_code: compile_regex(&labels.tax_code)?,
+            address_phone: compile_regex(&labels.address_phone)?,
+            bank_account: compile_regex(&labels.bank_account)?,
+        })
+    }
+
+    pub(crate) fn anchor(&self, is_seller_info: bool) -> &regex::Regex {
+        if is_seller_info {
+            &self.seller_anchor
+        } else {
+            &self.buyer_anchor
+        }
+    }
+}
+
+// 相对锚点的搜索区域：水平方向从锚点右边界算起，竖直方向从锚点基线算起
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Region {
+    // 锚点文本的正则，取第一个匹配的文本项
+    anchor: String,
+    #[serde(default)]
+    left: f64,
+    // 为空时延伸到页面右侧
+    #[serde(default)]
+    right: Option<f64>,
+    // 锚点上方、下方的范围
+    top: f64,
+    bottom: f64,
+    // 编译后的锚点正则，读取模板时生成
+    #[serde(skip)]
+    anchor_regex: Option<regex::Regex>,
+}
+
+impl Region {
+    // 区域内的文本项，按原始顺序排列；找不到锚点时返回 None
+    pub(crate) fn find_items<'a>(&self, text_items: &'a [TextItem]) -> Option<Vec<&'a TextItem>> {
+        let anchor_regex = self.anchor_regex.as_ref()?;
+        let anchor = text_items
+            .iter()
+            .find(|item| anchor_regex.is_match(&item.text))?;
+
+        let anchor_right = anchor.x + anchor.width;
+        let left = anchor_right + self.left;
+        let right = self.right.map(|right| anchor_right + right);
+        let top = anchor.y - self.top;
+        let bottom = anchor.y + self.bottom;
+        Some(
+            text_items
+                .iter()
+                .filter(|item| {
+                    item.x >= left
+                        && right.map(|right| item.x <= right).unwrap_or(true)
+                        && item.y >= top
+                        && item.y <= bottom
+                        && item.page_index == anchor.page_index
+                })
+                .collect(),
+        )
+    }
+}
+
+// 按区域提取的字段
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FieldRule {
+    // 字段路径，与人工修正的字段一致，如 "number"、"seller.name"
+    field: String,
+    #[serde(flatten)]
+    region: Region,
+    // 从区域文本中提取值的正则，有捕获组时取第一个捕获组，为空时取区域内的全部文本
+    #[serde(default)]
+    pattern: String,
+    // 编译后的提取正则，pattern 为空时为 None
+    #[serde(skip)]
+    pattern_regex: Option<regex::Regex>,
+}
+
+impl LayoutTemplate {
+    // 编译模板中的正则并检查字段，返回第一个错误；匹配页面时只使用编译后的正则
+    fn compile(&mut self) -> Result<(), String> {
+        let name = self.name.clone();
+        let compile_regex = |pattern: &str| {
+            regex::Regex::new(pattern)
+                .map_err(|e| format!("版式模板 {} 的正则 {} 无效: {}", name, pattern, e))
+        };
+
+        self.detect_regex = if self.detect.is_empty() {
+            None
+        } else {
+            Some(compile_regex(&self.detect)?)
+        };
+        if let Some(party) = &mut self.party {
+            party.regexes = Some(PartyRegexes::compile(
+                compile_regex,
+                &party.buyer_anchor,
+                &party.seller_anchor,
+                &party.labels,
+            )?);
+        }
+        if let Some(columns) = &mut self.party_columns {
+            columns.regexes = Some(PartyRegexes::compile(
+                compile_regex,
+                &columns.buyer_anchor,
+                &columns.seller_anchor,
+                &columns.labels,
+            )?);
+        }
+        if let Some(remark) = &mut self.remark {
+            remark.anchor_regex = Some(compile_regex(&remark.anchor)?);
+        }
+        for rule in &mut self.fields {
+            rule.region.anchor_regex = Some(compile_regex(&rule.region.anchor)?);
+            rule.pattern_regex = if rule.pattern.is_empty() {
+                None
+            } else {
+                Some(compile_regex(&rule.pattern)?)
+            };
+            let mut invoice = create_empty_invoice("", "", 0);
+            corrections::assign_field(&mut invoice, &rule.field, "")
+                .map_err(|e| format!("版式模板 {}: {}", name, e))?;
+        }
+        Ok(())
+    }
+
+    // 模板是否适用于该发票类型和页面
+    fn matches(&self, invoice_type: &str, page_text: &str) -> bool {
+        let type_matched = self.invoice_types.is_empty()
+            || self.invoice_types.iter().any(|name| name == invoice_type);
+        let text_matched = match &self.detect_regex {
+            Some(regex) => regex.is_match(page_text),
+            None => self.detect.is_empty(),
+        };
+        type_matched && text_matched
+    }
+
+    // 购买方、销售方信息区域
+    pub(crate) fn party(&self) -> Option<&PartyLayout> {
+        self.party.as_ref()
+    }
+
+    // 左右并列的购买方、销售方信息区域
+    pub(crate) fn party_columns(&self) -> Option<&PartyColumns> {
+        self.party_columns.as_ref()
+    }
+
+    // 备注区域
+    pub(crate) fn remark(&self) -> Option<&Region> {
+        self.remark.as_ref()
+    }
+}
+
+fn builtin_templates() -> Vec<LayoutTemplate> {
+    BUILTIN_TEMPLATES
+        .iter()
+        .map(|content| {
+            let mut template: LayoutTemplate = serde_json::from_str(content).unwrap();
+            template.compile().unwrap();
+            template
+        })
+        .collect()
+}
+
+fn store() -> &'static RwLock<Vec<Arc<LayoutTemplate>>> {
+    TEMPLATES.get_or_init(|| RwLock::new(builtin_templates().into_iter().map(Arc::new).collect()))
+}
+
+// 用户模板目录
+pub(crate) fn layout_dir(config_dir: &Path) -> PathBuf {
+    config_dir.join(LAYOUT_DIR_NAME)
+}
+
+// 应用默认的用户模板目录，供命令行工具使用
+pub(crate) fn default_layout_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| layout_dir(&dir.join(APP_IDENTIFIER)))
+}
+
+// 读取目录下的用户模板，按文件名排序；目录不存在时返回空列表
+// 无法读取或有错误的模板文件被跳过，其错误随读取到的模板一起返回
+pub(crate) fn load_templates(dir: &Path) -> (Vec<LayoutTemplate>, Vec<String>) {
+    if !dir.exists() {
+        return (Vec::new(), Vec::new());
+    }
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => return (Vec::new(), vec![format!("读取版式模板目录失败: {}", e)]),
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file()
+                && path
+                    .extension()
+                    .map(|ext| ext.eq_ignore_ascii_case("json"))
+                    .unwrap_or(false)
+        })
+        .collect();
+    paths.sort();
+
+    let mut templates = Vec::new();
+    let mut errors = Vec::new();
+    for path in paths {
+        match load_template(&path) {
+            Ok(template) => templates.push(template),
+            Err(e) => errors.push(e),
+        }
+    }
+    (templates, errors)
+}
+
+// 读取一个模板文件并编译其中的正则
+fn load_template(path: &Path) -> Result<LayoutTemplate, String> {
+    let content = fs::read_to_string(path)
+        .map_err(|e| format!("读取版式模板 {} 失败: {}", path.display(), e))?;
+    let mut template: LayoutTemplate = serde_json::from_str(&content)
+        .map_err(|e| format!("解析版式模板 {} 失败: {}", path.display(), e))?;
+    template
+        .compile()
+        .map_err(|e| format!("{}（{}）", e, path.display()))?;
+    template.path = path.display().to_string();
+    Ok(template)
+}
+
+// 使用用户模板，同名的内置模板被覆盖
+pub(crate) fn set_user_templates(user_templates: Vec<LayoutTemplate>) {
+    let mut templates: Vec<Arc<LayoutTemplate>> =
+        user_templates.into_iter().map(Arc::new).collect();
+    for builtin in builtin_templates() {
+        if !templates
+            .iter()
+            .any(|template| template.name == builtin.name)
+        {
+            templates.push(Arc::new(builtin));
+        }
+    }
+    if let Ok(mut store) = store().write() {
+        *store = templates;
+    }
+}
+
+// 当前使用的全部模板，按匹配顺序排列
+pub(crate) fn templates() -> Vec<Arc<LayoutTemplate>> {
+    store()
+        .read()
+        .map(|templates| templates.clone())
+        .unwrap_or_default()
+}
+
+// 在适用的模板中查找第一个定义了所需内容的模板，page_text 为去除空白后拼接的整页文本
+pub(crate) fn find(
+    invoice_type: &str,
+    page_text: &str,
+    defines: impl Fn(&LayoutTemplate) -> bool,
+) -> Option<Arc<LayoutTemplate>> {
+    let templates = store().read().ok()?;
+    templates
+        .iter()
+        .find(|template| defines(template) && template.matches(invoice_type, page_text))
+        .cloned()
+}
+
+// 定义了购买方、销售方信息区域的模板
+pub(crate) fn party_layout(invoice_type: &str, page_text: &str) -> Option<Arc<LayoutTemplate>> {
+    find(invoice_type, page_text, |template| template.party.is_some())
+}
+
+// 定义了左右并列的购买方、销售方信息区域的模板
+pub(crate) fn party_columns(invoice_type: &str, page_text: &str) -> Option<Arc<LayoutTemplate>> {
+    find(invoice_type, page_text, |template| {
+        template.party_columns.is_some()
+    })
+}
+
+// 定义了备注区域的模板
+pub(crate) fn remark_region(invoice_type: &str, page_text: &str) -> Option<Arc<LayoutTemplate>> {
+    find(invoice_type, page_text, |template| {
+        template.remark.is_some()
+    })
+}
+
+// 按第一个定义了字段规则的模板提取字段，提取到值时覆盖解析器识别的结果
+pub(crate) fn apply_field_rules(invoice: &mut Invoice, text_items: &[TextItem], page_text: &str) {
+    let template = match find(&invoice.invoice_type, page_text, |template| {
+        !template.fields.is_empty()
+    }) {
+        Some(template) => template,
+        None => return,
+    };
+
+    for rule in &template.fields {
+        let mut area_items = match rule.region.find_items(text_items) {
+            Some(items) if !items.is_empty() => items,
+            _ => continue,
+        };
+        let text = group_items_by_row(&area_items, 3.0)
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|item| item.text.as_str())
+                    .collect::<String>()
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        let value = if rule.pattern.is_empty() {
+            text.trim().to_string()
+        } else {
+            let pattern = match &rule.pattern_regex {
+                Some(pattern) => pattern,
+                None => continue,
+            };
+            match pattern.captures(&text) {
+                Some(caps) => caps
+                    .get(1)
+                    .or_else(|| caps.get(0))
+                    .map(|m| m.as_str().trim().to_string())
+                    .unwrap_or_default(),
+                None => continue,
+            }
+        };
+        if value.is_empty() || corrections::assign_field(invoice, &rule.field, &value).is_err() {
+            continue;
+        }
+
+        // 只保留组成该值的文本项作为来源
+        if !rule.pattern.is_empty() {
+            let matched: Vec<&TextItem> = area_items
+                .iter()
+                .filter(|item| !item.text.trim().is_empty() && value.contains(item.text.trim()))
+                .copied()
+                .collect();
+            if !matched.is_empty() {
+                area_items = matched;
+            }
+        }
+        provenance::record(
+            &mut invoice.sources,
+            &rule.field,
+            &area_items,
+            provenance::LAYOUT_SCORE,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_templates_skips_invalid_files() {
+        let dir = std::env::temp_dir().join(format!("layout_templates_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("a.json"),
+            r#"{"name":"旅游服务","detect":"旅游服务","remark":{"anchor":"^备$","top":5,"bottom":40}}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("b.json"), r#"{"name":"正则错误","detect":"("}"#).unwrap();
+        fs::write(
+            dir.join("c.json"),
+            r#"{"name":"字段错误","fields":[{"field":"nope","anchor":"x","top":1,"bottom":1}]}"#,
+        )
+        .unwrap();
+        fs::write(dir.join("d.json"), "{").unwrap();
+        fs::write(dir.join("readme.txt"), "").unwrap();
+
+        let (templates, errors) = load_templates(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<&str> = templates.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, vec!["旅游服务"]);
+        assert_eq!(errors.len(), 3);
+        assert!(errors[0].contains("正则错误") && errors[0].contains("b.json"));
+        assert!(errors[1].contains("nope") && errors[1].contains("c.json"));
+        assert!(errors[2].contains("d.json"));
+
+        let template = &templates[0];
+        assert!(template.matches("增值税电子普通发票", "*旅游服务*代订住宿"));
+        assert!(!template.matches("增值税电子普通发票", "*餐饮服务*餐费"));
+        assert!(template.remark().unwrap().anchor_regex.is_some());
+    }
+
+    #[test]
+    fn missing_dir_has_no_templates() {
+        let (templates, errors) = load_templates(Path::new("/nonexistent/layouts"));
+        assert!(templates.is_empty());
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn builtin_templates_are_compiled() {
+        for template in builtin_templates() {
+            if let Some(party) = template.party() {
+                assert!(party.regexes().is_some(), "{}", template.name);
+            }
+            if let Some(columns) = template.party_columns() {
+                assert!(columns.regexes().is_some(), "{}", template.name);
+            }
+            if let Some(remark) = template.remark() {
+                assert!(remark.anchor_regex.is_some(), "{}", template.name);
+            }
+        }
+    }
+}
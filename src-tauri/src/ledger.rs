@@ -0,0 +1,491 @@
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::{duplicate, money::Money, Invoice};
+
+// 台账数据库保存在应用数据目录下
+const LEDGER_FILE_NAME: &str = "invoice_ledger.db";
+
+const CREATE_TABLES: &str = "
+CREATE TABLE IF NOT EXISTS ledger (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    invoice_key TEXT,
+    code TEXT NOT NULL,
+    number TEXT NOT NULL,
+    date TEXT NOT NULL,
+    seller_name TEXT NOT NULL,
+    buyer_name TEXT NOT NULL,
+    total_amount_tax INTEGER,
+    filename TEXT NOT NULL,
+    file_hash TEXT NOT NULL,
+    page INTEGER NOT NULL,
+    batch TEXT NOT NULL,
+    imported_at TEXT NOT NULL,
+    reimbursed_date TEXT
+);
+CREATE INDEX IF NOT EXISTS ledger_invoice_key ON ledger (invoice_key);
+CREATE INDEX IF NOT EXISTS ledger_file_hash ON ledger (file_hash, page);
+";
+
+const SELECT_COLUMNS: &str = "id, code, number, date, seller_name, buyer_name, total_amount_tax, \
+     filename, file_hash, page, batch, imported_at, reimbursed_date";
+
+// 台账中的一条发票记录
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LedgerEntry {
+    id: i64,
+    code: String,
+    number: String,
+    date: String,
+    seller_name: String,
+    buyer_name: String,
+    total_amount_tax: Money,
+    filename: String,
+    file_hash: String,
+    page: usize,
+    // 导入批次
+    batch: String,
+    imported_at: String,
+    reimbursed: bool,
+    reimbursed_date: String,
+}
+
+impl LedgerEntry {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        let total_amount_tax: Option<i64> = row.get(6)?;
+        let reimbursed_date: Option<String> = row.get(12)?;
+        Ok(LedgerEntry {
+            id: row.get(0)?,
+            code: row.get(1)?,
+            number: row.get(2)?,
+            date: row.get(3)?,
+            seller_name: row.get(4)?,
+            buyer_name: row.get(5)?,
+            total_amount_tax: total_amount_tax.map_or(Money::MISSING, Money::from_cents),
+            filename: row.get(7)?,
+            file_hash: row.get(8)?,
+            page: row.get::<_, i64>(9)? as usize,
+            batch: row.get(10)?,
+            imported_at: row.get(11)?,
+            reimbursed: reimbursed_date.is_some(),
+            reimbursed_date: reimbursed_date.unwrap_or_default(),
+        })
+    }
+
+    pub(crate) fn batch(&self) -> &str {
+        &self.batch
+    }
+
+    // 重复说明，如"与台账中 20240506101530-123456789 批次导入的发票重复（已于2024-05-20报销）"
+    pub(crate) fn duplicate_info(&self) -> String {
+        let reimbursed = if self.reimbursed {
+            format!("（已于{}报销）", self.reimbursed_date)
+        } else {
+            "".to_string()
+        };
+        format!("与台账中 {} 批次导入的发票重复{}", self.batch, reimbursed)
+    }
+}
+
+// 台账查询条件，keyword 匹配发票代码、号码、销售方、购买方和文件名
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct LedgerQuery {
+    #[serde(default)]
+    keyword: String,
+    #[serde(default)]
+    batch: String,
+    #[serde(default)]
+    reimbursed: Option<bool>,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+// 发票台账，记录已确认为正常的发票，用于跨批次检查重复报销
+pub struct Ledger {
+    connection: Connection,
+}
+
+// 台账文件路径
+pub(crate) fn ledger_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(LEDGER_FILE_NAME)
+}
+
+// 新的导入批次号，使用当前时间，如 20240506101530-123456789
+// 附加纳秒，同一秒内的两次导入不会使用同一批次号，否则无法检查出两次导入间的重复
+pub(crate) fn new_batch() -> String {
+    chrono::Local::now().format("%Y%m%d%H%M%S-%9f").to_string()
+}
+
+// 转义 LIKE 模式中的通配符，配合 ESCAPE '\' 使用
+fn escape_like(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '%' | '_') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl Ledger {
+    // 打开台账，数据库不存在时创建
+    pub(crate) fn open(path: &Path) -> Result<Self, String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| format!("创建数据目录失败: {}", e))?;
+        }
+        let connection = Connection::open(path).map_err(|e| format!("打开发票台账失败: {}", e))?;
+        Self::with_connection(connection)
+    }
+
+    fn with_connection(connection: Connection) -> Result<Self, String> {
+        connection
+            .execute_batch(CREATE_TABLES)
+            .map_err(|e| format!("初始化发票台账失败: {}", e))?;
+        Ok(Ledger { connection })
+    }
+
+    // 查找其他批次中与该发票重复的记录，按发票号码或文件内容匹配
+    pub(crate) fn find_duplicate(
+        &self,
+        invoice: &Invoice,
+        batch: &str,
+    ) -> Result<Option<LedgerEntry>, String> {
+        let invoice_key = duplicate::number_key(invoice);
+        if invoice_key.is_none() && invoice.file_hash.is_empty() {
+            return Ok(None);
+        }
+
+        let sql = format!(
+            "SELECT {} FROM ledger WHERE batch != ?1 AND (invoice_key = ?2 OR (file_hash != '' AND file_hash = ?3 AND page = ?4)) ORDER BY id LIMIT 1",
+            SELECT_COLUMNS
+        );
+        self.connection
+            .query_row(
+                &sql,
+                params![batch, invoice_key, invoice.file_hash, invoice.page as i64],
+                LedgerEntry::from_row,
+            )
+            .optional()
+            .map_err(|e| format!("查询发票台账失败: {}", e))
+    }
+
+    // 查找红字发票冲红的正数发票，未注明发票代码时仅按号码匹配
+    pub(crate) fn find_original(
+        &self,
+        code: &str,
+        number: &str,
+    ) -> Result<Option<LedgerEntry>, String> {
+        if number.is_empty() {
+            return Ok(None);
+        }
+
+        let sql = format!(
+            "SELECT {} FROM ledger WHERE number = ?1 AND (?2 = '' OR code = '' OR code = ?2) AND (total_amount_tax IS NULL OR total_amount_tax > 0) ORDER BY id LIMIT 1",
+            SELECT_COLUMNS
+        );
+        self.connection
+            .query_row(&sql, params![number, code], LedgerEntry::from_row)
+            .optional()
+            .map_err(|e| format!("查询发票台账失败: {}", e))
+    }
+
+    // 将正常状态的发票记入台账，已记录过的发票不重复记录，返回新增记录数
+    pub(crate) fn record(&mut self, invoices: &[Invoice], batch: &str) -> Result<usize, String> {
+        let transaction = self
+            .connection
+            .transaction()
+            .map_err(|e| format!("写入发票台账失败: {}", e))?;
+        let mut count = 0;
+        {
+            let mut exists = transaction
+                .prepare(
+                    "SELECT 1 FROM ledger WHERE invoice_key = ?1 OR (file_hash != '' AND file_hash = ?2 AND page = ?3)",
+                )
+                .map_err(|e| format!("写入发票台账失败: {}", e))?;
+            let mut insert = transaction
+                .prepare(
+                    "INSERT INTO ledger (invoice_key, code, number, date, seller_name, buyer_name, total_amount_tax, filename, file_hash, page, batch, imported_at) \
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, datetime('now', 'localtime'))",
+                )
+                .map_err(|e| format!("写入发票台账失败: {}", e))?;
+
+            for invoice in invoices.iter().filter(|invoice| invoice.status == "正常") {
+                let invoice_key = duplicate::number_key(invoice);
+                let page = invoice.page as i64;
+                let recorded = exists
+                    .exists(params![invoice_key, invoice.file_hash, page])
+                    .map_err(|e| format!("写入发票台账失败: {}", e))?;
+                if recorded {
+                    continue;
+                }
+                insert
+                    .execute(params![
+                        invoice_key,
+                        invoice.code,
+                        invoice.number,
+                        invoice.date,
+                        invoice.seller.name,
+                        invoice.buyer.name,
+                        invoice.total_amount_tax.cents(),
+                        invoice.filename,
+                        invoice.file_hash,
+                        page,
+                        batch,
+                    ])
+                    .map_err(|e| format!("写入发票台账失败: {}", e))?;
+                count += 1;
+            }
+        }
+        transaction
+            .commit()
+            .map_err(|e| format!("写入发票台账失败: {}", e))?;
+        Ok(count)
+    }
+
+    // 查询台账，按记录顺序倒序返回
+    pub(crate) fn search(&self, query: &LedgerQuery) -> Result<Vec<LedgerEntry>, String> {
+        let mut conditions: Vec<&str> = Vec::new();
+        let mut values: Vec<String> = Vec::new();
+
+        let keyword = query.keyword.trim();
+        if !keyword.is_empty() {
+            conditions.push(
+                "(code LIKE ?1 ESCAPE '\\' OR number LIKE ?1 ESCAPE '\\' OR seller_name LIKE ?1 ESCAPE '\\' \
+                 OR buyer_name LIKE ?1 ESCAPE '\\' OR filename LIKE ?1 ESCAPE '\\')",
+            );
+            values.push(format!("%{}%", escape_like(keyword)));
+        }
+        if !query.batch.is_empty() {
+            conditions.push(if values.is_empty() {
+                "batch = ?1"
+            } else {
+                "batch = ?2"
+            });
+            values.push(query.batch.clone());
+        }
+        match query.reimbursed {
+            Some(true) => conditions.push("reimbursed_date IS NOT NULL"),
+            Some(false) => conditions.push("reimbursed_date IS NULL"),
+            None => {}
+        }
+
+        let mut sql = format!("SELECT {} FROM ledger", SELECT_COLUMNS);
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
+        }
+        sql.push_str(" ORDER BY id DESC");
+        if let Some(limit) = query.limit {
+            sql.push_str(&format!(" LIMIT {}", limit));
+        }
+
+        let mut statement = self
+            .connection
+            .prepare(&sql)
+            .map_err(|e| format!("查询发票台账失败: {}", e))?;
+        let entries = statement
+            .query_map(params_from_iter(values.iter()), LedgerEntry::from_row)
+            .map_err(|e| format!("查询发票台账失败: {}", e))?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|e| format!("查询发票台账失败: {}", e))?;
+        Ok(entries)
+    }
+
+    // 删除台账记录，返回删除的记录数
+    pub(crate) fn remove(&self, ids: &[i64]) -> Result<usize, String> {
+        let mut count = 0;
+        for id in ids {
+            count += self
+                .connection
+                .execute("DELETE FROM ledger WHERE id = ?1", params![id])
+                .map_err(|e| format!("删除台账记录失败: {}", e))?;
+        }
+        Ok(count)
+    }
+
+    // 标记已报销及报销日期（YYYY-MM-DD），日期为 None 时取消报销标记，返回更新的记录数
+    pub(crate) fn mark_reimbursed(&self, ids: &[i64], date: Option<&str>) -> Result<usize, String> {
+        let date = match date {
+            Some(date) => Some(
+                chrono::NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d")
+                    .map_err(|_| format!("报销日期格式错误: {}", date))?
+                    .format("%Y-%m-%d")
+                    .to_string(),
+            ),
+            None => None,
+        };
+
+        let mut count = 0;
+        for id in ids {
+            count += self
+                .connection
+                .execute(
+                    "UPDATE ledger SET reimbursed_date = ?1 WHERE id = ?2",
+                    params![date, id],
+                )
+                .map_err(|e| format!("更新报销状态失败: {}", e))?;
+        }
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::create_empty_invoice;
+
+    fn ledger() -> Ledger {
+        Ledger::with_connection(Connection::open_in_memory().unwrap()).unwrap()
+    }
+
+    fn invoice(number: &str, seller: &str, file_hash: &str) -> Invoice {
+        let mut invoice = create_empty_invoice("发票.pdf", "正常", 0);
+        invoice.code = "044001900111".to_string();
+        invoice.number = number.to_string();
+        invoice.seller.name = seller.to_string();
+        invoice.file_hash = file_hash.to_string();
+        invoice.total_amount_tax = Money::parse("113.00");
+        invoice
+    }
+
+    fn numbers(entries: &[LedgerEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.number.as_str()).collect()
+    }
+
+    #[test]
+    fn record_skips_recorded_and_abnormal_invoices() {
+        let mut ledger = ledger();
+        let mut failed = invoice("00000003", "丙公司", "hash3");
+        failed.status = "解析失败".to_string();
+        let invoices = vec![
+            invoice("00000001", "甲公司", "hash1"),
+            invoice("00000002", "乙公司", "hash2"),
+            failed,
+        ];
+        assert_eq!(ledger.record(&invoices, "batch1").unwrap(), 2);
+
+        // 号码相同或文件内容相同的发票不再记录
+        let again = vec![
+            invoice("00000001", "甲公司", "other"),
+            invoice("00000009", "乙公司", "hash2"),
+            invoice("00000004", "丁公司", "hash4"),
+        ];
+        assert_eq!(ledger.record(&again, "batch2").unwrap(), 1);
+        let entries = ledger.search(&LedgerQuery::default()).unwrap();
+        assert_eq!(numbers(&entries), vec!["00000004", "00000002", "00000001"]);
+    }
+
+    #[test]
+    fn find_duplicate_in_other_batches() {
+        let mut ledger = ledger();
+        ledger
+            .record(&[invoice("00000001", "甲公司", "hash1")], "batch1")
+            .unwrap();
+
+        let same_number = invoice("00000001", "甲公司", "other");
+        let entry = ledger
+            .find_duplicate(&same_number, "batch2")
+            .unwrap()
+            .unwrap();
+        assert_eq!(entry.batch(), "batch1");
+
+        let same_file = invoice("", "", "hash1");
+        assert!(ledger
+            .find_duplicate(&same_file, "batch2")
+            .unwrap()
+            .is_some());
+
+        // 同一批次内的记录不算重复
+        assert!(ledger
+            .find_duplicate(&same_number, "batch1")
+            .unwrap()
+            .is_none());
+        let other = invoice("00000002", "甲公司", "hash2");
+        assert!(ledger.find_duplicate(&other, "batch2").unwrap().is_none());
+    }
+
+    #[test]
+    fn new_batches_differ_within_a_second() {
+        assert_ne!(new_batch(), new_batch());
+    }
+
+    #[test]
+    fn search_by_keyword_and_batch() {
+        let mut ledger = ledger();
+        ledger
+            .record(
+                &[
+                    invoice("00000001", "甲公司", "hash1"),
+                    invoice("00000002", "乙_公司", "hash2"),
+                ],
+                "batch1",
+            )
+            .unwrap();
+        ledger
+            .record(&[invoice("00000003", "甲公司分公司", "hash3")], "batch2")
+            .unwrap();
+
+        let search = |keyword: &str, batch: &str| {
+            let query = LedgerQuery {
+                keyword: keyword.to_string(),
+                batch: batch.to_string(),
+                ..Default::default()
+            };
+            numbers(&ledger.search(&query).unwrap())
+                .into_iter()
+                .map(str::to_string)
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(search("甲公司", ""), vec!["00000003", "00000001"]);
+        assert_eq!(search("甲公司", "batch1"), vec!["00000001"]);
+        assert_eq!(search("00000002", ""), vec!["00000002"]);
+        // 通配符按字面匹配
+        assert_eq!(search("乙_公司", ""), vec!["00000002"]);
+        assert!(search("甲_司", "").is_empty());
+        assert!(search("%", "").is_empty());
+    }
+
+    #[test]
+    fn mark_and_unmark_reimbursed() {
+        let mut ledger = ledger();
+        ledger
+            .record(
+                &[
+                    invoice("00000001", "甲公司", "hash1"),
+                    invoice("00000002", "乙公司", "hash2"),
+                ],
+                "batch1",
+            )
+            .unwrap();
+        let entries = ledger.search(&LedgerQuery::default()).unwrap();
+        let first = entries
+            .iter()
+            .find(|entry| entry.number == "00000001")
+            .unwrap();
+
+        assert_eq!(
+            ledger
+                .mark_reimbursed(&[first.id], Some("2024-05-20"))
+                .unwrap(),
+            1
+        );
+        assert!(ledger
+            .mark_reimbursed(&[first.id], Some("2024/05/20"))
+            .is_err());
+        let query = LedgerQuery {
+            reimbursed: Some(true),
+            ..Default::default()
+        };
+        let reimbursed = ledger.search(&query).unwrap();
+        assert_eq!(numbers(&reimbursed), vec!["00000001"]);
+        assert_eq!(reimbursed[0].reimbursed_date, "2024-05-20");
+        assert!(reimbursed[0]
+            .duplicate_info()
+            .contains("已于2024-05-20报销"));
+
+        assert_eq!(ledger.mark_reimbursed(&[first.id], None).unwrap(), 1);
+        assert!(ledger.search(&query).unwrap().is_empty());
+    }
+}
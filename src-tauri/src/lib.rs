@@ -1,28 +1,59 @@
-use regex;
+// 部分函数和类型只由桌面应用的命令使用，仅构建命令行工具时不视为未使用
+#![cfg_attr(not(feature = "desktop"), allow(dead_code))]
+
+use rayon::prelude::*;
 use rust_xlsxwriter::{Color, Format, FormatBorder, Workbook};
 use serde::{Deserialize, Serialize};
 use std::{
     fs,
-    path::Path,
-    sync::{Arc, Mutex, OnceLock},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
 };
-use tauri::{AppHandle, State};
-use tauri_plugin_dialog::DialogExt;
-use tauri_plugin_opener::OpenerExt;
-use tauri_plugin_updater::UpdaterExt;
-// use tauri_plugin_notification::NotificationExt;
 
-// 定义一个全局静态变量来存储 AppHandle
-static APP: OnceLock<AppHandle> = OnceLock::new();
+use money::Money;
+
+mod air_itinerary;
+mod batch;
+mod buyer_whitelist;
+pub mod cli;
+mod confidence;
+mod corrections;
+mod dates;
+#[cfg(feature = "desktop")]
+mod desktop;
+mod digital_invoice;
+mod duplicate;
+mod jobs;
+mod layout;
+mod ledger;
+mod money;
+mod multi_page;
+mod pdf_text;
+mod provenance;
+mod query;
+mod red_letter;
+mod tax_id;
+mod train_ticket;
+mod validation;
+
+// 桌面应用入口，命令行工具使用 --no-default-features 构建时不包含
+#[cfg(feature = "desktop")]
+pub use desktop::run;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct InvoiceItem {
     name: String,
+    // 规格型号
+    #[serde(default)]
+    spec: String,
+    // 单位
+    #[serde(default)]
+    unit: String,
     quantity: String,
     price: String,
-    amount: String,
+    amount: Money,
     tax_rate: String,
-    tax: String,
+    tax: Money,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -34,7 +65,11 @@ pub struct Invoice {
     invoice_type: String,
     code: String,
     number: String,
+    // 票面上的开票日期文本
     date: String,
+    // 解析后的开票日期，无法识别时为 None
+    #[serde(default)]
+    date_value: Option<chrono::NaiveDate>,
     checksum: String,
     machine_number: String,
     password: String,
@@ -42,14 +77,60 @@ pub struct Invoice {
     buyer: InvoiceParty,
     seller: InvoiceParty,
     items: Vec<InvoiceItem>,
-    total_amount: String,
-    total_tax: String,
-    total_amount_tax: String,
+    total_amount: Money,
+    total_tax: Money,
+    total_amount_tax: Money,
+    // 价税合计（大写）原文及其对应金额
+    #[serde(default)]
+    total_amount_tax_upper: String,
+    #[serde(default)]
+    total_amount_tax_upper_value: Money,
     payee: String,
     reviewer: String,
     drawer: String,
     status: String,
     duplicate_info: String,
+    // 重复组编号，0 表示不重复
+    #[serde(default)]
+    duplicate_group: usize,
+    // 源文件内容的 SHA-256 和发票所在页码（从1开始），用于识别内容相同的文件
+    #[serde(default)]
+    file_hash: String,
+    #[serde(default)]
+    page: usize,
+    // 进项税额是否可抵扣（增值税专用发票）
+    #[serde(default)]
+    deductible: bool,
+    // 可抵扣进项税额
+    #[serde(default)]
+    deductible_tax: Money,
+    // 铁路电子客票信息
+    #[serde(default)]
+    train_ticket: Option<train_ticket::TrainTicket>,
+    // 航空运输电子客票行程单信息
+    #[serde(default)]
+    air_itinerary: Option<air_itinerary::AirItinerary>,
+    // 金额交叉校验结果
+    #[serde(default)]
+    validation: Vec<validation::ValidationFinding>,
+    // 购买方与本公司抬头白名单不符的原因
+    #[serde(default)]
+    buyer_mismatch: String,
+    // 人工修正记录
+    #[serde(default)]
+    corrections: Vec<corrections::Correction>,
+    // 各字段在PDF中的来源区域
+    #[serde(default)]
+    sources: provenance::FieldSources,
+    // 各字段及整张发票的置信度
+    #[serde(default)]
+    confidence: confidence::Confidence,
+    // 红字发票对应的正数发票，不是红字发票时为 None
+    #[serde(default)]
+    red_letter: Option<red_letter::RedLetter>,
+    // 冲红该发票的红字发票序号
+    #[serde(default)]
+    reversed_by: Vec<usize>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -58,6 +139,9 @@ pub struct InvoiceParty {
     tax_code: String,
     address_phone: String,
     bank_account: String,
+    // 纳税人识别号校验结果
+    #[serde(default)]
+    tax_code_status: String,
 }
 
 // 文本位置信息，对应前端的TextItem接口
@@ -76,80 +160,131 @@ pub struct TextItem {
 
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct ProcessingStats {
-    total_amount: String,
-    total_tax: String,
+    total_amount: Money,
+    total_tax: Money,
+    #[serde(default)]
+    total_amount_tax: Money,
     invoice_count: usize,
     duplicate_count: usize,
+    #[serde(default)]
+    suspected_duplicate_count: usize,
     success_count: usize,
     fail_count: usize,
+    #[serde(default)]
+    review_count: usize,
+    #[serde(default)]
+    buyer_mismatch_count: usize,
+    // 正常发票按税率、按发票类型分组的合计
+    #[serde(default)]
+    by_tax_rate: Vec<StatsBreakdown>,
+    #[serde(default)]
+    by_invoice_type: Vec<StatsBreakdown>,
+    // 正常发票中的红字发票数，其金额为负数，已在合计中与正数发票冲抵
+    #[serde(default)]
+    red_letter_count: usize,
 }
 
-struct ProcessingState {
-    invoices: Vec<Invoice>,
-    stats: ProcessingStats,
+// 分组合计，按税率分组时 count 为明细行数，按发票类型分组时为发票数
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct StatsBreakdown {
+    key: String,
+    count: usize,
+    amount: Money,
+    tax: Money,
+    amount_tax: Money,
 }
 
-impl Default for ProcessingState {
-    fn default() -> Self {
-        Self {
-            invoices: Vec::new(),
-            stats: ProcessingStats::default(),
-        }
-    }
+// 累加分组合计，金额以分为单位
+fn add_to_breakdown(
+    groups: &mut std::collections::BTreeMap<String, (usize, i64, i64, i64)>,
+    key: &str,
+    amount: Money,
+    tax: Money,
+    amount_tax: Money,
+) {
+    let key = if key.trim().is_empty() {
+        "未知"
+    } else {
+        key.trim()
+    };
+    let group = groups.entry(key.to_string()).or_default();
+    group.0 += 1;
+    group.1 += amount.cents().unwrap_or(0);
+    group.2 += tax.cents().unwrap_or(0);
+    group.3 += amount_tax.cents().unwrap_or(0);
 }
 
-type AppState = Arc<Mutex<ProcessingState>>;
+// 将分组合计转换为结果列表
+fn breakdown_list(
+    groups: std::collections::BTreeMap<String, (usize, i64, i64, i64)>,
+) -> Vec<StatsBreakdown> {
+    groups
+        .into_iter()
+        .map(|(key, (count, amount, tax, amount_tax))| StatsBreakdown {
+            key,
+            count,
+            amount: Money::from_cents(amount),
+            tax: Money::from_cents(tax),
+            amount_tax: Money::from_cents(amount_tax),
+        })
+        .collect()
+}
 
-// 解析PDF提取的文本位置信息
-#[tauri::command]
-async fn parse_invoice_text(
-    filename: &str,
-    pages_text_items: Vec<Vec<TextItem>>,
-) -> Result<Vec<Invoice>, String> {
-    // println!("接收到前端发来的文本解析请求: {}", filename);
-    // println!("共 {} 页文本数据", pages_text_items.len());
+// 读取并解析单个PDF文件
+fn parse_file(file_path: &Path) -> Result<Vec<Invoice>, String> {
+    parse_file_with_progress(file_path, &|_, _| {})
+}
 
-    // 检查输入数据
-    if pages_text_items.is_empty() {
-        return Err("没有接收到文本数据".to_string());
+// 读取并解析单个PDF文件，每解析完一页调用 on_page(已完成页数, 总页数)
+fn parse_file_with_progress(
+    file_path: &Path,
+    on_page: &(dyn Fn(usize, usize) + Sync),
+) -> Result<Vec<Invoice>, String> {
+    let filename = file_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| file_path.to_string_lossy().to_string());
+
+    let bytes = fs::read(file_path).map_err(|e| format!("无法读取文件: {}", e))?;
+    let file_hash = duplicate::file_hash(&bytes);
+    let pages_text_items = pdf_text::extract_pages_text_items(file_path)?;
+
+    let mut invoices = parse_pages_with_progress(&filename, &pages_text_items, on_page);
+    for invoice in &mut invoices {
+        invoice.file_hash = file_hash.clone();
     }
+    Ok(invoices)
+}
 
-    let mut all_parsed_results = Vec::new();
-
-    // 处理每一页
-    for (page_index, text_items) in pages_text_items.iter().enumerate() {
-        // println!("开始处理第 {} 页, 共 {} 个文本项", page_index + 1, text_items.len());
-
-        // 跳过空页面
-        if text_items.is_empty() {
-            println!("第 {} 页没有文本项，跳过处理", page_index + 1);
-            // 创建一个空的发票对象标记为解析失败
-            let mut empty_invoice = create_empty_invoice(filename, "解析失败", page_index);
-            empty_invoice.remark = "该页没有可识别的文本".to_string();
-            all_parsed_results.push(empty_invoice);
-            continue;
-        }
-
-        // 创建一个空的发票对象
-        let invoice = create_empty_invoice(filename, "待统计", page_index);
+// 逐页解析文本项，生成发票列表
+fn parse_pages(filename: &str, pages_text_items: &[Vec<TextItem>]) -> Vec<Invoice> {
+    parse_pages_with_progress(filename, pages_text_items, &|_, _| {})
+}
 
-        // 用try-catch包装解析过程，防止单页解析失败影响整体
-        let parsed_invoice = match std::panic::catch_unwind(|| {
-            parse_generic_fapiao(text_items, invoice.clone(), page_index)
-        }) {
-            Ok(invoice) => invoice,
-            Err(e) => {
-                println!("第 {} 页解析时发生错误: {:?}", page_index + 1, e);
-                let mut failed_invoice = invoice.clone();
-                failed_invoice.status = "解析失败".to_string();
-                failed_invoice.remark = "发票解析过程中出现错误".to_string();
-                failed_invoice
-            }
-        };
+// 并行解析各页文本项，结果按页码顺序返回，每解析完一页调用 on_page(已完成页数, 总页数)
+fn parse_pages_with_progress(
+    filename: &str,
+    pages_text_items: &[Vec<TextItem>],
+    on_page: &(dyn Fn(usize, usize) + Sync),
+) -> Vec<Invoice> {
+    let page_count = pages_text_items.len();
+    let finished_pages = AtomicUsize::new(0);
+
+    let all_parsed_results: Vec<Invoice> = pages_text_items
+        .par_iter()
+        .enumerate()
+        .map(|(page_index, text_items)| {
+            let invoice = parse_single_page(filename, page_index, text_items);
+            on_page(
+                finished_pages.fetch_add(1, Ordering::SeqCst) + 1,
+                page_count,
+            );
+            invoice
+        })
+        .collect();
 
-        // println!("第 {} 页解析完成，发票号码: {}", page_index + 1, parsed_invoice.number);
-        all_parsed_results.push(parsed_invoice);
-    }
+    // 多页发票和销货清单并入同一张发票
+    let mut all_parsed_results = multi_page::merge_pages(all_parsed_results, pages_text_items);
 
     // println!("文件 {} 解析完成，共 {} 页发票", filename, all_parsed_results.len());
 
@@ -160,7 +295,41 @@ async fn parse_invoice_text(
         all_parsed_results.push(failed_invoice);
     }
 
-    Ok(all_parsed_results)
+    all_parsed_results
+}
+
+// 解析单页文本项，空页面或解析出错时返回解析失败的发票
+fn parse_single_page(filename: &str, page_index: usize, text_items: &[TextItem]) -> Invoice {
+    // println!("开始处理第 {} 页, 共 {} 个文本项", page_index + 1, text_items.len());
+
+    // 跳过空页面
+    if text_items.is_empty() {
+        log::warn!("{} 第 {} 页没有文本项，跳过处理", filename, page_index + 1);
+        // 创建一个空的发票对象标记为解析失败
+        let mut empty_invoice = create_empty_invoice(filename, "解析失败", page_index);
+        empty_invoice.remark = "该页没有可识别的文本".to_string();
+        return empty_invoice;
+    }
+
+    // 创建一个空的发票对象
+    let invoice = create_empty_invoice(filename, "待统计", page_index);
+
+    // 用try-catch包装解析过程，防止单页解析失败影响整体
+    match std::panic::catch_unwind(|| parse_page(text_items, invoice.clone(), page_index)) {
+        Ok(invoice) => invoice,
+        Err(e) => {
+            log::error!(
+                "{} 第 {} 页解析时发生错误: {:?}",
+                filename,
+                page_index + 1,
+                e
+            );
+            let mut failed_invoice = invoice.clone();
+            failed_invoice.status = "解析失败".to_string();
+            failed_invoice.remark = "发票解析过程中出现错误".to_string();
+            failed_invoice
+        }
+    }
 }
 
 // 创建空的发票对象
@@ -180,6 +349,7 @@ fn create_empty_invoice(filename: &str, status: &str, page_index: usize) -> Invo
         code: "".to_string(),
         number: "".to_string(),
         date: "".to_string(),
+        date_value: None,
         checksum: "".to_string(),
         machine_number: "".to_string(),
         password: "".to_string(),
@@ -189,38 +359,56 @@ fn create_empty_invoice(filename: &str, status: &str, page_index: usize) -> Invo
             tax_code: "".to_string(),
             address_phone: "".to_string(),
             bank_account: "".to_string(),
+            tax_code_status: "".to_string(),
         },
         seller: InvoiceParty {
             name: "".to_string(),
             tax_code: "".to_string(),
             address_phone: "".to_string(),
             bank_account: "".to_string(),
+            tax_code_status: "".to_string(),
         },
         items: Vec::new(),
-        total_amount: "0.00".to_string(),
-        total_tax: "0.00".to_string(),
-        total_amount_tax: "0.00".to_string(),
+        total_amount: Money::MISSING,
+        total_tax: Money::MISSING,
+        total_amount_tax: Money::MISSING,
+        total_amount_tax_upper: "".to_string(),
+        total_amount_tax_upper_value: Money::MISSING,
         payee: "".to_string(),
         reviewer: "".to_string(),
         drawer: "".to_string(),
         status: status.to_string(),
         duplicate_info: "".to_string(),
+        duplicate_group: 0,
+        file_hash: "".to_string(),
+        page: page_index + 1,
+        deductible: false,
+        deductible_tax: Money::MISSING,
+        train_ticket: None,
+        air_itinerary: None,
+        validation: Vec::new(),
+        buyer_mismatch: "".to_string(),
+        corrections: Vec::new(),
+        sources: provenance::FieldSources::new(),
+        confidence: confidence::Confidence::default(),
+        red_letter: None,
+        reversed_by: Vec::new(),
     }
 }
 
-// 根据关键词提取相邻文本
-fn extract_nearby_text(
-    text_items: &[TextItem],
+// 根据关键词提取相邻文本，同时返回组成该文本的文本项及与标签的匹配程度
+fn extract_nearby_text_with_source<'a>(
+    text_items: &'a [TextItem],
     reference_text: &regex::Regex,
     direction: &str,
     max_distance: f64,
-) -> String {
+) -> provenance::Extracted<'a> {
     // 找到参考文本项
     let ref_item = text_items
         .iter()
         .find(|item| reference_text.is_match(&item.text));
     if ref_item.is_none() {
-        return "".to_string();
+        return provenance::Extracted::empty();
     }
 
     let ref_item = ref_item.unwrap();
@@ -283,25 +471,89 @@ fn extract_nearby_text(
     }
 
     // 取最接近的文本或合并多个文本，过滤掉包含冒号的文本
-    candidates
-        .iter()
+    let source: Vec<&TextItem> = candidates
+        .into_iter()
         .filter(|item| !item.text.contains(':') && !item.text.contains('：'))
-        .map(|item| item.text.clone())
-        .collect::<Vec<String>>()
+        .collect();
+    let text = source
+        .iter()
+        .map(|item| item.text.as_str())
+        .collect::<Vec<&str>>()
         .join(" ")
         .trim()
-        .to_string()
+        .to_string();
+    provenance::Extracted {
+        value: text,
+        label_score: provenance::label_score(ref_item, reference_text, &source, max_distance),
+        items: source,
+    }
+}
+
+// 提取标签对应的值：值与标签在同一文本项中（如"发票号码：123"）时直接截取，否则查找右侧相邻文本
+fn extract_labeled_value(
+    text_items: &[TextItem],
+    label: &regex::Regex,
+    max_distance: f64,
+) -> String {
+    extract_labeled_value_with_source(text_items, label, max_distance).value
+}
+
+// 提取标签对应的值，同时返回值所在的文本项及与标签的匹配程度
+fn extract_labeled_value_with_source<'a>(
+    text_items: &'a [TextItem],
+    label: &regex::Regex,
+    max_distance: f64,
+) -> provenance::Extracted<'a> {
+    let label_item = text_items.iter().find(|item| label.is_match(&item.text));
+    if let Some(label_item) = label_item {
+        let inline_value = label.replace(&label_item.text, "").trim().to_string();
+        if !inline_value.is_empty() {
+            // 值与标签在同一文本项中
+            return provenance::Extracted {
+                value: inline_value,
+                items: vec![label_item],
+                label_score: 1.0,
+            };
+        }
+    }
+
+    extract_nearby_text_with_source(text_items, label, "right", max_distance)
+}
+
+// 去除空白后拼接整页文本，用于按页面文本匹配版式模板等
+pub(crate) fn page_text(text_items: &[TextItem]) -> String {
+    text_items
+        .iter()
+        .flat_map(|item| item.text.chars())
+        .filter(|c| !c.is_whitespace())
+        .collect()
 }
 
 // 提取发票购买方和销售方信息
 fn extract_party_info(
     text_items: &[TextItem],
+    page_text: &str,
     invoice: &mut Invoice,
-    header_char: &str,
     is_seller_info: bool,
 ) {
+    // 区域偏移量和字段标签取自版式模板
+    let template = match layout::party_layout(&invoice.invoice_type, page_text) {
+        Some(template) => template,
+        None => return,
+    };
+    let (layout, regexes) = match template
+        .party()
+        .and_then(|layout| Some((layout, layout.regexes()?)))
+    {
+        Some(layout) => layout,
+        None => return,
+    };
+
     // 标识字符 (购/销)
-    let header_item = text_items.iter().find(|item| item.text == header_char);
+    let header_regex = regexes.anchor(is_seller_info);
+    let header_item = text_items
+        .iter()
+        .find(|item| header_regex.is_match(&item.text));
     if header_item.is_none() {
         return;
     }
@@ -335,54 +587,43 @@ fn extract_party_info(
 
     let footer_item = footer_item.unwrap();
     // 区域坐标偏移量
-    let offset_x_left = if invoice.invoice_type == "普通发票" {
-        8.0
-    } else {
-        15.0
-    };
-    let offset_x_right = if invoice.invoice_type == "普通发票" {
-        250.0
-    } else {
-        160.0
-    };
-    let offset_y = if invoice.invoice_type == "普通发票" {
-        0.0
-    } else {
-        8.0
-    };
+    let (offset_x_left, offset_x_right, offset_y) = (layout.left, layout.right, layout.vertical);
+
+    // 专用发票的信息区域右侧紧邻密码区，不能越过密码区标签
+    let mut area_right_x = header_x.floor() + offset_x_right;
+    if layout.stop_at_password {
+        if let Some(password_item) = text_items.iter().find(|item| {
+            item.text == "密" && item.x > header_x && item.page_index == header_item.page_index
+        }) {
+            area_right_x = area_right_x.min(password_item.x - 1.0);
+        }
+    }
 
     // 信息区域坐标
     let area_left_top = (
-        (header_x as f64).floor() + offset_x_left,
-        (header_y as f64).floor() - offset_y,
-    );
-    let area_right_top = (
-        (header_x as f64).floor() + offset_x_right,
-        (header_y as f64).floor() - offset_y,
-    );
-    let area_right_bottom = (
-        (footer_item.x as f64).floor() + offset_x_right,
-        (footer_item.y as f64).floor() + offset_y,
+        header_x.floor() + offset_x_left,
+        header_y.floor() - offset_y,
     );
+    let area_right_top = (area_right_x, header_y.floor() - offset_y);
+    let area_right_bottom = (area_right_x, footer_item.y.floor() + offset_y);
     // 查找区域内的所有文本项
     let area_items: Vec<&TextItem> = text_items
         .iter()
         .filter(|item| {
             item.x >= area_left_top.0
                 && item.x <= area_right_top.0
-                && item.x >= area_left_top.0
                 && item.y >= area_left_top.1
                 && item.y <= area_right_bottom.1
                 && item.page_index == footer_item.page_index
         })
         .collect();
-    // 获取特定字段的值
-    let get_field_value = |label_pattern: &regex::Regex| -> String {
+    // 获取特定字段的值及其所在的文本项
+    let get_field_value = |label_pattern: &regex::Regex| -> provenance::Extracted {
         let label_item = area_items
             .iter()
             .find(|item| label_pattern.is_match(&item.text));
         if label_item.is_none() {
-            return "".to_string();
+            return provenance::Extracted::empty();
         }
 
         let label_item = label_item.unwrap();
@@ -392,62 +633,146 @@ fn extract_party_info(
         let label_right = label_x + label_width;
 
         let mut result = String::new();
+        let mut source = Vec::new();
         for item in &area_items {
             if item.x + item.width > label_right
-                && (item.y - label_y).abs() <= 6.0
+                && (item.y - label_y).abs() <= layout.row_tolerance
                 && !item.text.contains(':')
                 && !item.text.contains('：')
             {
                 result.push_str(&item.text);
+                source.push(*item);
             }
         }
 
-        result
+        provenance::Extracted {
+            value: result,
+            label_score: provenance::label_score(
+                label_item,
+                label_pattern,
+                &source,
+                offset_x_right - offset_x_left,
+            ),
+            items: source,
+        }
     };
 
     // 设置到相应的对象
-    let party_obj = if is_seller_info {
-        &mut invoice.seller
+    let (party_obj, prefix) = if is_seller_info {
+        (&mut invoice.seller, "seller")
     } else {
-        &mut invoice.buyer
+        (&mut invoice.buyer, "buyer")
     };
+    let sources = &mut invoice.sources;
 
     // 提取名称
-    party_obj.name = get_field_value(&regex::Regex::new(r"称[:：]?$").unwrap());
+    party_obj.name = provenance::record_value(
+        sources,
+        &format!("{}.name", prefix),
+        get_field_value(&regexes.name),
+    );
 
     // 提取纳税人识别号
-    party_obj.tax_code = get_field_value(&regex::Regex::new(r"识别号[:：]?$").unwrap());
+    party_obj.tax_code = provenance::record_value(
+        sources,
+        &format!("{}.tax_code", prefix),
+        get_field_value(&regexes.tax_code),
+    );
 
     // 提取地址、电话
-    party_obj.address_phone = get_field_value(&regex::Regex::new(r"电话[:：]?$").unwrap());
+    party_obj.address_phone = provenance::record_value(
+        sources,
+        &format!("{}.address_phone", prefix),
+        get_field_value(&regexes.address_phone),
+    );
 
     // 提取开户行及账号
-    party_obj.bank_account = get_field_value(&regex::Regex::new(r"开户行及账号[:：]?$").unwrap());
+    party_obj.bank_account = provenance::record_value(
+        sources,
+        &format!("{}.bank_account", prefix),
+        get_field_value(&regexes.bank_account),
+    );
 }
 
-// 提取备注信息
-fn extract_remark_info(text_items: &[TextItem], invoice: &mut Invoice) {
-    // 查找参考项
-    let header_item = text_items.iter().find(|item| item.text == "备");
+// 是否为增值税专用发票（进项税额可抵扣）
+fn is_special_invoice_type(invoice_type: &str) -> bool {
+    invoice_type.contains("专用发票")
+}
+
+// 确定可抵扣标记和可抵扣税额：专用发票和客票可抵扣，可抵扣税额即票面税额
+// 客票由解析器标记为可抵扣，税额已按扣除率计算；解析完成或人工修正发票类型、税额后调用
+fn derive_deductible(invoice: &mut Invoice) {
+    if invoice.train_ticket.is_none() && invoice.air_itinerary.is_none() {
+        invoice.deductible = is_special_invoice_type(&invoice.invoice_type);
+    }
+    invoice.deductible_tax = if invoice.deductible {
+        invoice.total_tax
+    } else {
+        Money::MISSING
+    };
+}
+
+// 提取密码区内容
+fn extract_password_info(text_items: &[TextItem], invoice: &mut Invoice) {
+    // 密码区标签为竖排的"密"、"码"、"区"
+    let header_item = text_items.iter().find(|item| item.text == "密");
     if header_item.is_none() {
         return;
     }
 
     let header_item = header_item.unwrap();
-    let hw = header_item.x + header_item.width;
-    let lt = header_item.y - 14.0;
-    let lb = header_item.y + 33.0;
+    let footer_y = text_items
+        .iter()
+        .find(|item| {
+            item.text == "区"
+                && (item.x - header_item.x).abs() <= 2.0
+                && item.y > header_item.y
+                && item.page_index == header_item.page_index
+        })
+        .map(|item| item.y)
+        .unwrap_or(header_item.y + 40.0);
+
+    let left = header_item.x + header_item.width;
+    let top = header_item.y - 8.0;
+    let bottom = footer_y + 8.0;
 
-    // 查询区域内的所有文本
     let area_items: Vec<&TextItem> = text_items
         .iter()
         .filter(|item| {
-            item.x >= hw && // 右侧
-        item.y >= lt && // 最上侧边界
-        item.y <= lb // 最下侧边界
+            item.x >= left
+                && item.y >= top
+                && item.y <= bottom
+                && item.page_index == header_item.page_index
         })
         .collect();
 
+    provenance::record(
+        &mut invoice.sources,
+        "password",
+        &area_items,
+        provenance::LAYOUT_SCORE,
+    );
+    invoice.password = group_items_by_row(&area_items, 2.0)
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|item| item.text.as_str())
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+}
+
+// 提取备注信息
+fn extract_remark_info(text_items: &[TextItem], page_text: &str, invoice: &mut Invoice) {
+    // 备注区域取自版式模板，以"备"字为参考项
+    let area_items = match layout::remark_region(&invoice.invoice_type, page_text)
+        .and_then(|template| template.remark()?.find_items(text_items))
+    {
+        Some(items) => items,
+        None => return,
+    };
+
     let mut result = String::new();
     for (i, r) in area_items.iter().enumerate() {
         if i > 0 && area_items[i - 1].y != r.y {
@@ -456,6 +781,12 @@ fn extract_remark_info(text_items: &[TextItem], invoice: &mut Invoice) {
         result.push_str(&r.text);
     }
 
+    provenance::record(
+        &mut invoice.sources,
+        "remark",
+        &area_items,
+        provenance::LAYOUT_SCORE,
+    );
     invoice.remark = result;
 }
 
@@ -490,32 +821,185 @@ fn group_items_by_row<'a>(items: &'a [&'a TextItem], y_tolerance: f64) -> Vec<Ve
     rows
 }
 
+// 按行拼接文本，同一行内以空格分隔，兼容 pdf.js 拆分文本项的情况
+fn join_rows(text_items: &[TextItem]) -> Vec<String> {
+    let items: Vec<&TextItem> = text_items.iter().collect();
+    group_items_by_row(&items, 3.0)
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|item| item.text.as_str())
+                .collect::<Vec<&str>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+// 在所有行中查找第一个匹配的捕获组
+fn capture_first(rows: &[String], pattern: &regex::Regex, group: usize) -> String {
+    rows.iter()
+        .find_map(|row| pattern.captures(row))
+        .and_then(|caps| caps.get(group))
+        .map(|m| m.as_str().trim().to_string())
+        .unwrap_or_default()
+}
+
+// 商品明细表的列
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum ItemColumn {
+    // 销货清单的序号列，不作为明细字段
+    Index,
+    Name,
+    Spec,
+    Unit,
+    Quantity,
+    Price,
+    Amount,
+    TaxRate,
+    Tax,
+}
+
+// 商品明细表头，纸质发票的表头可能拆分为单个字符
+const ITEM_HEADER_LABELS: [&str; 14] = [
+    "序号",
+    "货物或应税劳务、服务名称",
+    "货物（劳务）名称",
+    "货物(劳务)名称",
+    "货物或应税劳务名称",
+    "项目名称",
+    "规格型号",
+    "单位",
+    "数量",
+    "单价",
+    "金额",
+    "税率/征收率",
+    "征收率",
+    "税额",
+];
+
+// 根据表头文字判断所在列
+fn item_header_column(text: &str) -> Option<ItemColumn> {
+    if text.contains("序号") {
+        Some(ItemColumn::Index)
+    } else if text.contains("名称") {
+        Some(ItemColumn::Name)
+    } else if text.contains("规格") {
+        Some(ItemColumn::Spec)
+    } else if text.contains("单位") {
+        Some(ItemColumn::Unit)
+    } else if text.contains("数量") {
+        Some(ItemColumn::Quantity)
+    } else if text.contains("单价") {
+        Some(ItemColumn::Price)
+    } else if text.contains("金额") {
+        Some(ItemColumn::Amount)
+    } else if text.contains("税率") || text.contains("征收率") {
+        Some(ItemColumn::TaxRate)
+    } else if text.contains("税额") {
+        Some(ItemColumn::Tax)
+    } else {
+        None
+    }
+}
+
+// 识别表头行中的各列及其水平范围，相邻两列以表头之间的中点为界，首尾两列向外延伸
+fn item_header_columns(header_row: &[&TextItem]) -> Vec<(ItemColumn, f64, f64)> {
+    // 拼接拆分的表头文字，拼接后仍是某个表头的开头时视为同一单元格
+    let mut cells: Vec<(String, f64, f64)> = Vec::new();
+    for item in header_row {
+        if let Some(last) = cells.last_mut() {
+            let joined = format!("{}{}", last.0, item.text);
+            if ITEM_HEADER_LABELS
+                .iter()
+                .any(|label| label.starts_with(&joined))
+            {
+                last.0 = joined;
+                last.2 = item.x + item.width;
+                continue;
+            }
+        }
+        cells.push((item.text.clone(), item.x, item.x + item.width));
+    }
+
+    let mut headers: Vec<(ItemColumn, f64, f64)> = Vec::new();
+    for (text, left, right) in cells {
+        if let Some(column) = item_header_column(&text) {
+            if !headers.iter().any(|(existing, _, _)| *existing == column) {
+                headers.push((column, left, right));
+            }
+        }
+    }
+    headers.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    (0..headers.len())
+        .map(|i| {
+            let left = if i == 0 {
+                f64::NEG_INFINITY
+            } else {
+                (headers[i - 1].2 + headers[i].1) / 2.0
+            };
+            let right = if i + 1 == headers.len() {
+                f64::INFINITY
+            } else {
+                (headers[i].2 + headers[i + 1].1) / 2.0
+            };
+            (headers[i].0, left, right)
+        })
+        .collect()
+}
+
+// 按水平重叠程度确定文本项所在的列，宽度为0时按起始位置
+fn item_column(columns: &[(ItemColumn, f64, f64)], item: &TextItem) -> Option<ItemColumn> {
+    let right = item.x + item.width;
+    columns
+        .iter()
+        .map(|(column, left_bound, right_bound)| {
+            let overlap = right.min(*right_bound) - item.x.max(*left_bound);
+            (*column, overlap)
+        })
+        .filter(|(_, overlap)| *overlap > 0.0)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(column, _)| column)
+        .or_else(|| {
+            columns
+                .iter()
+                .find(|(_, left_bound, right_bound)| item.x >= *left_bound && item.x < *right_bound)
+                .map(|(column, _, _)| *column)
+        })
+}
+
 // 提取发票商品信息
 fn extract_invoice_items(text_items: &[TextItem], invoice: &mut Invoice) {
-    // 查找商品表头行 - 通常包含"货物名称"、"规格型号"、"单位"、"数量"等字段
-    let name_header_item = text_items
-        .iter()
-        .find(|item| item.text.contains("货物") || item.text.contains("项目"));
+    // 查找商品表头行 - 通常包含"货物名称"、"规格型号"、"单位"、"数量"等字段，跳过销货清单标题
+    let name_header_item = text_items.iter().find(|item| {
+        (item.text.contains("货物") || item.text.contains("项目")) && !item.text.contains("清单")
+    });
 
     if name_header_item.is_none() {
         // 没有找到表头，添加一个空的商品项
         invoice.items.push(InvoiceItem {
             name: "未能识别".to_string(),
+            spec: "".to_string(),
+            unit: "".to_string(),
             quantity: "0".to_string(),
             price: "0".to_string(),
-            amount: "0".to_string(),
+            amount: Money::MISSING,
             tax_rate: "0".to_string(),
-            tax: "0".to_string(),
+            tax: Money::MISSING,
         });
         return;
     }
 
     let name_header_item = name_header_item.unwrap();
 
-    // 确定表格的垂直范围 - 通常表头下方到"合计"行之前
+    // 确定表格的垂直范围 - 通常表头下方到"合计"行之前，销货清单为"小计"、"总计"行之前
     let model_bottom_item = text_items
         .iter()
-        .find(|item| item.text == "合" || item.text == "合计");
+        .filter(|item| {
+            matches!(item.text.as_str(), "合" | "合计" | "小计" | "总计")
+                && item.y > name_header_item.y
+        })
+        .min_by(|a, b| a.y.total_cmp(&b.y));
 
     if model_bottom_item.is_none() {
         return;
@@ -540,6 +1024,15 @@ fn extract_invoice_items(text_items: &[TextItem], invoice: &mut Invoice) {
         })
         .collect();
 
+    // 表头行，按表头位置划分各列
+    let mut header_row: Vec<&TextItem> = area_items
+        .iter()
+        .filter(|item| (item.y - name_header_item.y).abs() <= 3.0)
+        .copied()
+        .collect();
+    header_row.sort_by(|a, b| a.x.total_cmp(&b.x));
+    let columns = item_header_columns(&header_row);
+
     // 排除标题区域
     let no_title = name_header_item.y + 5.0;
     let value_items: Vec<&TextItem> = area_items
@@ -551,85 +1044,94 @@ fn extract_invoice_items(text_items: &[TextItem], invoice: &mut Invoice) {
     // 分组
     let grouped_rows = group_items_by_row(&value_items, 2.0);
 
-    let mut flag_map = std::collections::HashMap::new();
-
-    for (i, row) in grouped_rows.iter().enumerate() {
-        // 防止索引越界
-        if row.is_empty() {
+    let amount_regex = regex::Regex::new(r"^[¥￥]?-?[\d.]+$").unwrap();
+    for row in &grouped_rows {
+        // 将同一列的文本项合并为一个单元格
+        let mut cells: std::collections::BTreeMap<ItemColumn, Vec<&TextItem>> =
+            std::collections::BTreeMap::new();
+        for text_item in row {
+            match item_column(&columns, text_item) {
+                Some(ItemColumn::Index) | None => {}
+                Some(column) => cells.entry(column).or_default().push(text_item),
+            }
+        }
+        if cells.is_empty() {
             continue;
         }
+        let cell_text = |column: ItemColumn| -> String {
+            cells
+                .get(&column)
+                .map(|items| items.iter().map(|item| item.text.as_str()).collect())
+                .unwrap_or_default()
+        };
 
-        // 判断是否是补充名称行（只有一个字段且不以 * 开头）
-        if row.len() <= 3 && !row[0].text.starts_with('*') && i > 0 && !invoice.items.is_empty() {
+        // 判断是否是补充名称行（没有数量、金额等数值且不以 * 开头），追加到上一项
+        let has_values = cells.keys().any(|column| {
+            !matches!(
+                column,
+                ItemColumn::Name | ItemColumn::Spec | ItemColumn::Unit
+            )
+        });
+        if !has_values && !cell_text(ItemColumn::Name).starts_with('*') && !invoice.items.is_empty()
+        {
             let last_index = invoice.items.len() - 1;
-            invoice.items[last_index].name.push_str(&row[0].text);
+            let last = &mut invoice.items[last_index];
+            for (column, items) in &cells {
+                let (target, field) = match column {
+                    ItemColumn::Name => (&mut last.name, "name"),
+                    ItemColumn::Spec => (&mut last.spec, "spec"),
+                    _ => (&mut last.unit, "unit"),
+                };
+                target.push_str(&cell_text(*column));
+                provenance::append(
+                    &mut invoice.sources,
+                    &format!("items[{}].{}", last_index, field),
+                    items,
+                );
+            }
             continue; // 当前行不作为新的一项
         }
 
-        // 初始化新项目
-        let mut result = InvoiceItem {
-            name: "".to_string(),
-            quantity: "".to_string(),
-            price: "".to_string(),
-            amount: "".to_string(),
-            tax_rate: "".to_string(),
-            tax: "".to_string(),
+        let amount = cell_text(ItemColumn::Amount);
+        let tax = cell_text(ItemColumn::Tax);
+        let result = InvoiceItem {
+            name: cell_text(ItemColumn::Name),
+            spec: cell_text(ItemColumn::Spec),
+            unit: cell_text(ItemColumn::Unit),
+            quantity: cell_text(ItemColumn::Quantity),
+            price: cell_text(ItemColumn::Price),
+            amount: if amount_regex.is_match(&amount) {
+                Money::parse(&amount)
+            } else {
+                Money::MISSING
+            },
+            tax_rate: cell_text(ItemColumn::TaxRate),
+            tax: if amount_regex.is_match(&tax) {
+                Money::parse(&tax)
+            } else {
+                Money::MISSING
+            },
         };
 
-        // 正常行解析
-        for (index, text_item) in row.iter().enumerate() {
-            let value = &text_item.text;
-            let item_ptr = std::ptr::from_ref::<TextItem>(*text_item) as usize;
-
-            // 名称（通常是第一列，可能以 * 开头）
-            if index == 0 {
-                result.name = value.clone();
-            }
-
-            if row.len() > 5 && (index == row.len() - 5 || index == row.len() - 4) {
-                // 数量，只有当还没设置时才赋值
-                let is_quantity_format = regex::Regex::new(r"^\d+$").unwrap().is_match(value);
-                if is_quantity_format && !flag_map.contains_key(&item_ptr) {
-                    flag_map.insert(item_ptr, 1);
-                    result.quantity = value.clone();
-                }
-
-                // 金额，只有当还没设置时才赋值
-                let is_price_format = regex::Regex::new(r"^[¥￥]?-?[\d.]+$")
-                    .unwrap()
-                    .is_match(value);
-                if is_price_format && !flag_map.contains_key(&item_ptr) {
-                    flag_map.insert(item_ptr, 1);
-                    result.price = value.clone();
-                }
-            }
-
-            if row.len() > 3 && index == row.len() - 3 {
-                let is_amount_format = regex::Regex::new(r"^[¥￥]?-?[\d.]+$")
-                    .unwrap()
-                    .is_match(value);
-                if is_amount_format {
-                    // 金额
-                    result.amount = value.clone();
-                }
-            }
-
-            if row.len() > 2 && index == row.len() - 2 && value.contains('%') {
-                // 税率
-                result.tax_rate = value.clone();
-            }
-
-            if row.len() > 1 && index == row.len() - 1 {
-                let is_tax_format = regex::Regex::new(r"^[¥￥]?-?[\d.]+$")
-                    .unwrap()
-                    .is_match(value);
-                if is_tax_format {
-                    // 税额
-                    result.tax = value.clone();
-                }
+        for (column, field) in [
+            (ItemColumn::Name, "name"),
+            (ItemColumn::Spec, "spec"),
+            (ItemColumn::Unit, "unit"),
+            (ItemColumn::Quantity, "quantity"),
+            (ItemColumn::Price, "price"),
+            (ItemColumn::Amount, "amount"),
+            (ItemColumn::TaxRate, "tax_rate"),
+            (ItemColumn::Tax, "tax"),
+        ] {
+            if let Some(items) = cells.get(&column) {
+                provenance::record(
+                    &mut invoice.sources,
+                    &format!("items[{}].{}", invoice.items.len(), field),
+                    items,
+                    provenance::LAYOUT_SCORE,
+                );
             }
         }
-
         invoice.items.push(result);
     }
 
@@ -637,11 +1139,13 @@ fn extract_invoice_items(text_items: &[TextItem], invoice: &mut Invoice) {
     if invoice.items.is_empty() {
         invoice.items.push(InvoiceItem {
             name: "未能识别的商品".to_string(),
+            spec: "".to_string(),
+            unit: "".to_string(),
             quantity: "0".to_string(),
             price: "0".to_string(),
-            amount: "0".to_string(),
+            amount: Money::MISSING,
             tax_rate: "0".to_string(),
-            tax: "0".to_string(),
+            tax: Money::MISSING,
         });
     }
 }
@@ -668,51 +1172,152 @@ fn extract_total_amount_and_tax(text_items: &[TextItem], invoice: &mut Invoice)
     let mut sorted_items = same_line_items.clone();
     sorted_items.sort_by(|a, b| a.x.partial_cmp(&b.x).unwrap());
 
-    let mut values: Vec<String> = Vec::new();
+    // 金额及其所在的文本项
+    let mut values: Vec<(Money, &TextItem)> = Vec::new();
 
+    // 负号只能出现一次，可在货币符号前或后，如"-¥100.00"、"¥-100.00"
+    let amount_regex = regex::Regex::new(r"^-?[¥￥]?\d+(\.\d+)?$|^[¥￥]-\d+(\.\d+)?$").unwrap();
+    let number_regex = regex::Regex::new(r"^-?\d+(\.\d+)?$").unwrap();
+    // 负号有误的金额单元格，如"-¥-100.00"，仍占据所在位置，值为缺失
+    let malformed_regex = regex::Regex::new(r"^[¥￥]*-[-¥￥]*\d+(\.\d+)?$").unwrap();
     let mut i = 0;
     while i < sorted_items.len() {
         let text = &sorted_items[i].text;
-        let is_amount_format = regex::Regex::new(r"^[¥￥]?\d+(\.\d+)?$")
-            .unwrap()
-            .is_match(text);
-
-        if is_amount_format {
-            values.push(text.replace(|c| c == '¥' || c == '￥', ""));
-        } else if text == "¥" || text == "￥" {
-            if i + 1 < sorted_items.len() {
-                let next_text = &sorted_items[i + 1].text;
-                let is_next_amount = regex::Regex::new(r"^\d+(\.\d+)?$")
-                    .unwrap()
-                    .is_match(next_text);
-                if is_next_amount {
-                    values.push(next_text.clone());
-                    i += 1; // 跳过下一个已处理
-                }
-            }
+        if amount_regex.is_match(text) {
+            values.push((Money::parse(text), sorted_items[i]));
+        } else if malformed_regex.is_match(text) {
+            values.push((Money::MISSING, sorted_items[i]));
+        } else if (text == "¥" || text == "￥")
+            && i + 1 < sorted_items.len()
+            && number_regex.is_match(&sorted_items[i + 1].text)
+        {
+            values.push((Money::parse(&sorted_items[i + 1].text), sorted_items[i + 1]));
+            i += 1; // 跳过下一个已处理
         }
 
         i += 1;
     }
 
-    if !values.is_empty() {
-        // 合计金额
-        invoice.total_amount = values[0].clone();
-        // 合计税额
-        if values.len() > 1 {
-            invoice.total_tax = values[1].clone();
+    // 合计金额、合计税额按位置对应
+    for ((value, item), (target, field)) in values.iter().zip([
+        (&mut invoice.total_amount, "total_amount"),
+        (&mut invoice.total_tax, "total_tax"),
+    ]) {
+        *target = *value;
+        if !value.is_missing() {
+            provenance::record(
+                &mut invoice.sources,
+                field,
+                &[*item],
+                provenance::LAYOUT_SCORE,
+            );
         }
     }
 
     // 提取合计税价
     let tax_regex = regex::Regex::new(r"[（(]?小写[)）]?").unwrap();
-    invoice.total_amount_tax = extract_nearby_text(text_items, &tax_regex, "right", 100.0)
-        .replace(|c| c == '¥' || c == '￥', "");
+    let amount_tax = extract_nearby_text_with_source(text_items, &tax_regex, "right", 100.0);
+    invoice.total_amount_tax = Money::parse(&amount_tax.value);
+    if !invoice.total_amount_tax.is_missing() {
+        provenance::record(
+            &mut invoice.sources,
+            "total_amount_tax",
+            &amount_tax.items,
+            amount_tax.label_score,
+        );
+    }
+
+    // 提取价税合计（大写），只保留大写金额用字，去掉"ⓧ"等符号
+    let upper_regex = regex::Regex::new(r"[（(]大写[)）]").unwrap();
+    let upper = extract_nearby_text_with_source(text_items, &upper_regex, "right", 250.0);
+    provenance::record(
+        &mut invoice.sources,
+        "total_amount_tax_upper",
+        &upper.items,
+        upper.label_score,
+    );
+    invoice.total_amount_tax_upper = upper
+        .value
+        .split("小写")
+        .next()
+        .unwrap_or("")
+        .chars()
+        .filter(|c| "零壹贰叁肆伍陆柒捌玖拾佰仟万亿圆元角分整正负".contains(*c))
+        .collect();
+    invoice.total_amount_tax_upper_value = Money::parse_uppercase(&invoice.total_amount_tax_upper);
+
+    // 小写金额缺失或被拆分时，使用大写金额
+    if invoice.total_amount_tax.is_missing() {
+        invoice.total_amount_tax = invoice.total_amount_tax_upper_value;
+        provenance::record(
+            &mut invoice.sources,
+            "total_amount_tax",
+            &upper.items,
+            upper.label_score,
+        );
+    }
+}
+
+// 根据版式选择对应的解析器
+fn parse_page(text_items: &[TextItem], invoice: Invoice, page_index: usize) -> Invoice {
+    // 行程单和铁路电子客票同样没有发票代码，需要先于全电发票判断；
+    // 行程单的"电子客票号码"与铁路客票的"电子客票号"相近，需要先判断行程单
+    // 整页文本只拼接一次，供各版式模板匹配
+    let page_text = page_text(text_items);
+    let mut invoice = if air_itinerary::is_air_itinerary(text_items) {
+        air_itinerary::parse_air_itinerary_fapiao(text_items, invoice, page_index)
+    } else if train_ticket::is_train_ticket(text_items) {
+        train_ticket::parse_train_ticket_fapiao(text_items, invoice, page_index)
+    } else if digital_invoice::is_fully_digital(text_items) {
+        digital_invoice::parse_fully_digital_fapiao(text_items, &page_text, invoice, page_index)
+    } else {
+        parse_generic_fapiao(text_items, &page_text, invoice, page_index)
+    };
+
+    // 按版式模板中的字段规则提取，覆盖解析器识别的结果
+    layout::apply_field_rules(&mut invoice, text_items, &page_text);
+
+    // 版式模板可能修正发票类型，重新确定可抵扣税额
+    derive_deductible(&mut invoice);
+
+    // 识别红字发票
+    red_letter::detect(&mut invoice, text_items);
+
+    // 补充解析器没有记录来源的字段
+    provenance::fill_missing(&mut invoice, text_items);
+
+    check_invoice(&mut invoice);
+    invoice
+}
+
+// 解析开票日期、校验纳税人识别号、核对金额并计算置信度，解析完成或人工修正后调用
+fn check_invoice(invoice: &mut Invoice) {
+    invoice.date_value = dates::parse_date(&invoice.date);
+
+    // 校验购买方、销售方纳税人识别号
+    invoice.buyer.tax_code_status = tax_id::check_tax_code(&invoice.buyer.tax_code);
+    invoice.seller.tax_code_status = tax_id::check_tax_code(&invoice.seller.tax_code);
+
+    // 核对金额，发现问题时在去重阶段标记为需复核
+    invoice.validation = validation::validate_invoice(invoice);
+
+    invoice.confidence = confidence::evaluate(invoice);
+}
+
+// 旅客运输服务的增值税扣除率（9%）
+const TRANSPORT_TAX_RATE_PERCENT: i64 = 9;
+
+// 计算旅客运输服务可抵扣进项税额：金额 ÷ (1 + 9%) × 9%，四舍五入到分
+fn transport_deductible_tax_cents(amount_cents: i64) -> i64 {
+    let numerator = amount_cents * TRANSPORT_TAX_RATE_PERCENT * 2;
+    let denominator = (100 + TRANSPORT_TAX_RATE_PERCENT) * 2;
+    (numerator + denominator / 2) / denominator
 }
 
 // 根据通用发票格式解析发票信息
 fn parse_generic_fapiao(
     text_items: &[TextItem],
+    page_text: &str,
     mut invoice: Invoice,
     page_index: usize,
 ) -> Invoice {
@@ -720,27 +1325,29 @@ fn parse_generic_fapiao(
     invoice.index = page_index + 1;
 
     // 根据关键词提取标题
-    let title_regex = regex::Regex::new(r"电[⼦子]\S*").unwrap();
+    let title_regex = regex::Regex::new(r"电[⼦子]\S*|\S*专用发票").unwrap();
     let title_item = text_items
         .iter()
         .find(|item| title_regex.is_match(&item.text));
 
     if let Some(title_item) = title_item {
-        if title_item.text.contains("增值") {
-            invoice.title = if page_index == 0 {
-                title_item.text.clone()
-            } else {
-                format!("{} (第{}页)", title_item.text, page_index + 1)
-            };
-            invoice.invoice_type = "增值税电子普通发票".to_string();
+        invoice.title = if page_index == 0 {
+            title_item.text.clone()
         } else {
-            invoice.title = if page_index == 0 {
-                title_item.text.clone()
+            format!("{} (第{}页)", title_item.text, page_index + 1)
+        };
+        invoice.invoice_type = if title_item.text.contains("专用发票") {
+            // 纸质扫描件标题不含"电子"
+            if title_item.text.contains('电') {
+                "增值税电子专用发票".to_string()
             } else {
-                format!("{} (第{}页)", title_item.text, page_index + 1)
-            };
-            invoice.invoice_type = "普通发票".to_string();
-        }
+                "增值税专用发票".to_string()
+            }
+        } else if title_item.text.contains("增值") {
+            "增值税电子普通发票".to_string()
+        } else {
+            "普通发票".to_string()
+        };
     } else {
         // 如果没有找到标题，至少设置页码信息
         invoice.title = if page_index == 0 {
@@ -750,40 +1357,82 @@ fn parse_generic_fapiao(
         };
     }
 
+    // 专用发票的进项税额可抵扣
+    invoice.deductible = is_special_invoice_type(&invoice.invoice_type);
+
     // 提取发票代码
     let code_regex = regex::Regex::new(r"发票代码[:：]?").unwrap();
-    invoice.code = extract_nearby_text(text_items, &code_regex, "right", 100.0);
+    invoice.code = provenance::record_value(
+        &mut invoice.sources,
+        "code",
+        extract_nearby_text_with_source(text_items, &code_regex, "right", 100.0),
+    );
 
     // 提取发票号码
     let number_regex = regex::Regex::new(r"发票号码[:：]?").unwrap();
-    invoice.number = extract_nearby_text(text_items, &number_regex, "right", 100.0);
+    invoice.number = provenance::record_value(
+        &mut invoice.sources,
+        "number",
+        extract_nearby_text_with_source(text_items, &number_regex, "right", 100.0),
+    );
 
     // 提取开票日期
     let date_regex = regex::Regex::new(r"开票日期[:：]?").unwrap();
-    invoice.date = extract_nearby_text(text_items, &date_regex, "right", 150.0);
+    invoice.date = provenance::record_value(
+        &mut invoice.sources,
+        "date",
+        extract_nearby_text_with_source(text_items, &date_regex, "right", 150.0),
+    );
 
     // 提取校验码
     let checksum_regex = regex::Regex::new(r"^校验码[:：]|^码[:：]").unwrap();
-    invoice.checksum = extract_nearby_text(text_items, &checksum_regex, "right", 250.0);
+    invoice.checksum = provenance::record_value(
+        &mut invoice.sources,
+        "checksum",
+        extract_nearby_text_with_source(text_items, &checksum_regex, "right", 250.0),
+    );
+
+    // 提取机器编号
+    let machine_number_regex = regex::Regex::new(r"^机器编号[:：]?").unwrap();
+    invoice.machine_number = provenance::record_value(
+        &mut invoice.sources,
+        "machine_number",
+        extract_labeled_value_with_source(text_items, &machine_number_regex, 100.0),
+    );
+
+    // 提取密码区
+    extract_password_info(text_items, &mut invoice);
 
     // 提取购买方信息
-    extract_party_info(text_items, &mut invoice, "购", false);
+    extract_party_info(text_items, page_text, &mut invoice, false);
 
     // 提取销售方信息
-    extract_party_info(text_items, &mut invoice, "销", true);
+    extract_party_info(text_items, page_text, &mut invoice, true);
 
     // 提取开票人、收款人、复核人
     let drawer_regex = regex::Regex::new(r"^开票.{0,1}[:：]$").unwrap();
-    invoice.drawer = extract_nearby_text(text_items, &drawer_regex, "right", 100.0);
+    invoice.drawer = provenance::record_value(
+        &mut invoice.sources,
+        "drawer",
+        extract_nearby_text_with_source(text_items, &drawer_regex, "right", 100.0),
+    );
 
     let payee_regex = regex::Regex::new(r"^收款.{0,1}[:：]$").unwrap();
-    invoice.payee = extract_nearby_text(text_items, &payee_regex, "right", 100.0);
+    invoice.payee = provenance::record_value(
+        &mut invoice.sources,
+        "payee",
+        extract_nearby_text_with_source(text_items, &payee_regex, "right", 100.0),
+    );
 
     let reviewer_regex = regex::Regex::new(r"^复核.{0,1}[:：]$").unwrap();
-    invoice.reviewer = extract_nearby_text(text_items, &reviewer_regex, "right", 100.0);
+    invoice.reviewer = provenance::record_value(
+        &mut invoice.sources,
+        "reviewer",
+        extract_nearby_text_with_source(text_items, &reviewer_regex, "right", 100.0),
+    );
 
     // 提取备注
-    extract_remark_info(text_items, &mut invoice);
+    extract_remark_info(text_items, page_text, &mut invoice);
 
     // 提取商品信息
     extract_invoice_items(text_items, &mut invoice);
@@ -794,100 +1443,59 @@ fn parse_generic_fapiao(
     invoice
 }
 
-async fn update(app: tauri::AppHandle) -> tauri_plugin_updater::Result<()> {
-  if let Some(update) = app.updater()?.check().await? {
-    let mut downloaded = 0;
-
-    // 另外，我们也可以单独调用update.download（）和update.install（）
-    update
-      .download_and_install(
-        |chunk_length, content_length| {
-          downloaded += chunk_length;
-          println!("downloaded {downloaded} from {content_length:?}");
-        },
-        || {
-          println!("download finished");
-        },
-      )
-      .await?;
-
-    println!("update installed");
-    app.restart();
-  }
-
-  Ok(())
-}
-
-// 选择输出路径
-#[tauri::command]
-async fn select_output_path(app: tauri::AppHandle) -> Result<String, String> {
-    // 只允许选择文件夹
-    let desktop = dirs::desktop_dir().ok_or("无法找到桌面目录")?;
-    let selected_path = app
-        .dialog()
-        .file()
-        .set_directory(desktop)
-        .set_title("选择文件目录")
-        .blocking_pick_folder()
-        .ok_or("已取消选择目录")?;
-    Ok(selected_path.to_string())
+// 默认导出字段
+fn default_export_fields() -> Vec<String> {
+    vec![
+        "序号".to_string(),
+        "文件名".to_string(),
+        "状态".to_string(),
+        "发票类型".to_string(),
+        "发票代码".to_string(),
+        "发票号码".to_string(),
+        "开票日期".to_string(),
+        "购买方名称".to_string(),
+        "购买方税号".to_string(),
+        "购买方税号校验".to_string(),
+        "购买方地址、电话".to_string(),
+        "购买方开户行账号".to_string(),
+        "销售方名称".to_string(),
+        "销售方税号".to_string(),
+        "销售方税号校验".to_string(),
+        "销售方地址电话".to_string(),
+        "销售方开户行账号".to_string(),
+        "收款人".to_string(),
+        "复核人".to_string(),
+        "开票人".to_string(),
+        "金额".to_string(),
+        "税额".to_string(),
+        "价税合计".to_string(),
+        "价税合计(大写)".to_string(),
+        "可抵扣".to_string(),
+        "可抵扣税额".to_string(),
+        "备注".to_string(),
+        "重复信息".to_string(),
+        "校验信息".to_string(),
+        "抬头校验".to_string(),
+        "人工修正".to_string(),
+        "置信度".to_string(),
+        "红冲信息".to_string(),
+    ]
 }
 
-// 导出结果
-#[tauri::command]
-fn export_results(
-    path: &str,
-    filename: Option<&str>,
-    export_with_details: Option<bool>,
-    export_fields: Option<Vec<String>>, // 添加导出字段参数
-    state: State<AppState>,
-) -> Result<(), String> {
-    let processing_state = state
-        .lock()
-        .map_err(|_| "Failed to lock state".to_string())?;
-
-    if processing_state.invoices.is_empty() {
-        return Err("没有可导出的发票数据".to_string());
-    }
-
+// 将发票数据写入Excel工作簿，返回生成的文件路径
+fn write_workbook(
+    invoices: &[Invoice],
+    output_path: &Path,
+    file_name: &str,
+    include_details: bool,
+    fields_to_export: &[String],
+    job: Option<&jobs::Job>,
+) -> Result<PathBuf, String> {
     // 使用rust_xlsxwriter导出Excel
-    let output_path = Path::new(path);
     if !output_path.exists() {
         fs::create_dir_all(output_path).map_err(|e| format!("创建目录失败: {}", e))?;
     }
 
-    // 使用提供的文件名或默认名称
-    let file_name = filename.unwrap_or("发票数据汇总");
-    let include_details = export_with_details.unwrap_or(false);
-
-    // 获取要导出的字段列表，如果未提供则使用默认值
-    let fields_to_export = export_fields.unwrap_or_else(|| {
-        vec![
-            "序号".to_string(),
-            "文件名".to_string(),
-            "状态".to_string(),
-            "发票代码".to_string(),
-            "发票号码".to_string(),
-            "开票日期".to_string(),
-            "购买方名称".to_string(),
-            "购买方税号".to_string(),
-            "购买方地址、电话".to_string(),
-            "购买方开户行账号".to_string(),
-            "销售方名称".to_string(),
-            "销售方税号".to_string(),
-            "销售方地址电话".to_string(),
-            "销售方开户行账号".to_string(),
-            "收款人".to_string(),
-            "复核人".to_string(),
-            "开票人".to_string(),
-            "金额".to_string(),
-            "税额".to_string(),
-            "价税合计".to_string(),
-            "备注".to_string(),
-            "重复信息".to_string(),
-        ]
-    });
-
     // 创建Excel工作簿
     let mut workbook = Workbook::new();
 
@@ -899,11 +1507,7 @@ fn export_results(
 
     // 创建数据格式
     let data_format = Format::new().set_border(FormatBorder::Thin);
-
-    // 创建重复项格式（红色背景）
-    let duplicate_format = Format::new()
-        .set_border(FormatBorder::Thin)
-        .set_background_color(Color::RGB(0xFFCCCB)); // 红色背景
+    let date_format = data_format.clone().set_num_format(dates::EXCEL_DATE_FORMAT);
 
     // 创建不同重复组的颜色
     let duplicate_colors = [
@@ -914,9 +1518,15 @@ fn export_results(
         Color::RGB(0xD8BFD8), // 浅紫色
     ];
 
-    // 创建重复组格式映射
-    let mut duplicate_group_formats = std::collections::HashMap::new();
-    let mut duplicate_group_count = 0;
+    // 创建重复组格式，按重复组编号循环使用颜色
+    let duplicate_group_formats: Vec<Format> = duplicate_colors
+        .iter()
+        .map(|color| {
+            Format::new()
+                .set_border(FormatBorder::Thin)
+                .set_background_color(*color)
+        })
+        .collect();
 
     // 创建汇总表和明细表（如果需要）
     let main_sheet_name = "发票汇总";
@@ -951,15 +1561,18 @@ fn export_results(
         ("序号", 10),
         ("文件名", 30),
         ("状态", 10),
+        ("发票类型", 25),
         ("发票代码", 20),
         ("发票号码", 20),
         ("开票日期", 15),
         ("购买方名称", 30),
         ("购买方税号", 25),
+        ("购买方税号校验", 15),
         ("购买方地址、电话", 40),
         ("购买方开户行账号", 40),
         ("销售方名称", 30),
         ("销售方税号", 25),
+        ("销售方税号校验", 15),
         ("销售方地址电话", 40),
         ("销售方开户行账号", 40),
         ("收款人", 15),
@@ -968,8 +1581,29 @@ fn export_results(
         ("金额", 15),
         ("税额", 15),
         ("价税合计", 15),
+        ("价税合计(大写)", 30),
+        ("可抵扣", 10),
+        ("可抵扣税额", 15),
+        ("乘车人", 15),
+        ("证件号", 25),
+        ("出发站", 15),
+        ("到达站", 15),
+        ("车次", 10),
+        ("席别", 10),
+        ("乘车日期", 20),
+        ("旅客姓名", 15),
+        ("电子客票号码", 20),
+        ("航段", 40),
+        ("票价", 15),
+        ("民航发展基金", 15),
+        ("燃油附加费", 15),
         ("备注", 30),
         ("重复信息", 20),
+        ("校验信息", 50),
+        ("抬头校验", 40),
+        ("人工修正", 50),
+        ("置信度", 10),
+        ("红冲信息", 50),
     ];
 
     // 设置主工作表列宽和表头
@@ -1009,18 +1643,24 @@ fn export_results(
                 .map_err(|e| format!("设置明细列宽失败: {}", e))?; // 项目名称
             worksheet
                 .set_column_width(4, 15)
+                .map_err(|e| format!("设置明细列宽失败: {}", e))?; // 规格型号
+            worksheet
+                .set_column_width(5, 10)
+                .map_err(|e| format!("设置明细列宽失败: {}", e))?; // 单位
+            worksheet
+                .set_column_width(6, 15)
                 .map_err(|e| format!("设置明细列宽失败: {}", e))?; // 数量
             worksheet
-                .set_column_width(5, 15)
+                .set_column_width(7, 15)
                 .map_err(|e| format!("设置明细列宽失败: {}", e))?; // 单价
             worksheet
-                .set_column_width(6, 15)
+                .set_column_width(8, 15)
                 .map_err(|e| format!("设置明细列宽失败: {}", e))?; // 金额
             worksheet
-                .set_column_width(7, 15)
+                .set_column_width(9, 15)
                 .map_err(|e| format!("设置明细列宽失败: {}", e))?; // 税率
             worksheet
-                .set_column_width(8, 15)
+                .set_column_width(10, 15)
                 .map_err(|e| format!("设置明细列宽失败: {}", e))?; // 税额
 
             // 写入明细表头
@@ -1037,60 +1677,46 @@ fn export_results(
                 .write_string_with_format(0, 3, "项目名称", &header_format.clone())
                 .map_err(|e| format!("写入明细表头失败: {}", e))?;
             worksheet
-                .write_string_with_format(0, 4, "数量", &header_format.clone())
+                .write_string_with_format(0, 4, "规格型号", &header_format.clone())
                 .map_err(|e| format!("写入明细表头失败: {}", e))?;
             worksheet
-                .write_string_with_format(0, 5, "单价", &header_format.clone())
+                .write_string_with_format(0, 5, "单位", &header_format.clone())
                 .map_err(|e| format!("写入明细表头失败: {}", e))?;
             worksheet
-                .write_string_with_format(0, 6, "金额", &header_format.clone())
+                .write_string_with_format(0, 6, "数量", &header_format.clone())
                 .map_err(|e| format!("写入明细表头失败: {}", e))?;
             worksheet
-                .write_string_with_format(0, 7, "税率", &header_format.clone())
+                .write_string_with_format(0, 7, "单价", &header_format.clone())
                 .map_err(|e| format!("写入明细表头失败: {}", e))?;
             worksheet
-                .write_string_with_format(0, 8, "税额", &header_format.clone())
+                .write_string_with_format(0, 8, "金额", &header_format.clone())
+                .map_err(|e| format!("写入明细表头失败: {}", e))?;
+            worksheet
+                .write_string_with_format(0, 9, "税率", &header_format.clone())
+                .map_err(|e| format!("写入明细表头失败: {}", e))?;
+            worksheet
+                .write_string_with_format(0, 10, "税额", &header_format.clone())
                 .map_err(|e| format!("写入明细表头失败: {}", e))?;
-        }
-    }
-
-    // 按照发票代码和发票号码分组，创建颜色组
-    let mut duplicate_groups = std::collections::HashMap::new();
-
-    for invoice in &processing_state.invoices {
-        if invoice.status == "重复" {
-            let key = format!("{}-{}", invoice.code, invoice.number);
-            if !duplicate_groups.contains_key(&key) {
-                duplicate_groups.insert(key, duplicate_group_count);
-                // 创建对应颜色格式
-                let color_index = duplicate_group_count % duplicate_colors.len();
-                let format = Format::new()
-                    .set_border(FormatBorder::Thin)
-                    .set_background_color(duplicate_colors[color_index]);
-                duplicate_group_formats.insert(duplicate_group_count, format);
-                duplicate_group_count += 1;
-            }
         }
     }
 
     // 写入汇总数据
     if let Ok(worksheet) = workbook.worksheet_from_index(0) {
-        let mut row = 1;
-
-        for invoice in &processing_state.invoices {
-            // 确定格式
-            let mut format = data_format.clone();
-            if invoice.status == "重复" {
-                let key = format!("{}-{}", invoice.code, invoice.number);
-                if let Some(&group_id) = duplicate_groups.get(&key) {
-                    if let Some(group_format) = duplicate_group_formats.get(&group_id) {
-                        format = group_format.clone();
-                    } else {
-                        format = duplicate_format.clone();
-                    }
-                }
+        for (row, invoice) in (1..).zip(invoices) {
+            // 已取消时停止导出，不保存文件
+            if let Some(job) = job {
+                job.check_cancelled()?;
+                job.advance();
             }
 
+            // 确定格式，同一重复组使用相同颜色
+            let format = if invoice.duplicate_group > 0 {
+                let color_index = (invoice.duplicate_group - 1) % duplicate_group_formats.len();
+                duplicate_group_formats[color_index].clone()
+            } else {
+                data_format.clone()
+            };
+
             // 获取每个字段的值，根据字段名称
             for (idx, field) in fields_to_export.iter().enumerate() {
                 match field.as_str() {
@@ -1114,6 +1740,16 @@ fn export_results(
                             .write_string_with_format(row, idx as u16, &invoice.status, &format)
                             .map_err(|e| format!("写入状态失败: {}", e))?;
                     }
+                    "发票类型" => {
+                        worksheet
+                            .write_string_with_format(
+                                row,
+                                idx as u16,
+                                &invoice.invoice_type,
+                                &format,
+                            )
+                            .map_err(|e| format!("写入发票类型失败: {}", e))?;
+                    }
                     "发票代码" => {
                         worksheet
                             .write_string_with_format(row, idx as u16, &invoice.code, &format)
@@ -1125,9 +1761,22 @@ fn export_results(
                             .map_err(|e| format!("写入发票号码失败: {}", e))?;
                     }
                     "开票日期" => {
-                        worksheet
-                            .write_string_with_format(row, idx as u16, &invoice.date, &format)
-                            .map_err(|e| format!("写入开票日期失败: {}", e))?;
+                        // 能识别的日期写入为日期单元格，否则写入原文
+                        match invoice.date_value.and_then(dates::to_excel_date) {
+                            Some(date) => worksheet.write_datetime_with_format(
+                                row,
+                                idx as u16,
+                                &date,
+                                &format.clone().set_num_format(dates::EXCEL_DATE_FORMAT),
+                            ),
+                            None => worksheet.write_string_with_format(
+                                row,
+                                idx as u16,
+                                &invoice.date,
+                                &format,
+                            ),
+                        }
+                        .map_err(|e| format!("写入开票日期失败: {}", e))?;
                     }
                     "购买方名称" => {
                         worksheet
@@ -1144,6 +1793,16 @@ fn export_results(
                             )
                             .map_err(|e| format!("写入购买方税号失败: {}", e))?;
                     }
+                    "购买方税号校验" => {
+                        worksheet
+                            .write_string_with_format(
+                                row,
+                                idx as u16,
+                                &invoice.buyer.tax_code_status,
+                                &format,
+                            )
+                            .map_err(|e| format!("写入购买方税号校验失败: {}", e))?;
+                    }
                     "购买方地址、电话" => {
                         worksheet
                             .write_string_with_format(
@@ -1184,6 +1843,16 @@ fn export_results(
                             )
                             .map_err(|e| format!("写入销售方税号失败: {}", e))?;
                     }
+                    "销售方税号校验" => {
+                        worksheet
+                            .write_string_with_format(
+                                row,
+                                idx as u16,
+                                &invoice.seller.tax_code_status,
+                                &format,
+                            )
+                            .map_err(|e| format!("写入销售方税号校验失败: {}", e))?;
+                    }
                     "销售方地址电话" => {
                         worksheet
                             .write_string_with_format(
@@ -1220,65 +1889,128 @@ fn export_results(
                             .map_err(|e| format!("写入开票人失败: {}", e))?;
                     }
                     "金额" => {
-                        // 尝试将金额转换为数字，如果失败则保持字符串格式
-                        match invoice.total_amount.parse::<f64>() {
-                            Ok(amount) => {
+                        // 金额写入为数字，缺失时留空
+                        match invoice.total_amount.to_f64() {
+                            Some(amount) => {
                                 worksheet
                                     .write_number_with_format(row, idx as u16, amount, &format)
                                     .map_err(|e| format!("写入金额失败: {}", e))?;
                             }
-                            Err(_) => {
+                            None => {
                                 worksheet
-                                    .write_string_with_format(
-                                        row,
-                                        idx as u16,
-                                        &invoice.total_amount,
-                                        &format,
-                                    )
+                                    .write_blank(row, idx as u16, &format)
                                     .map_err(|e| format!("写入金额失败: {}", e))?;
                             }
                         }
                     }
                     "税额" => {
-                        // 尝试将税额转换为数字，如果失败则保持字符串格式
-                        match invoice.total_tax.parse::<f64>() {
-                            Ok(tax) => {
+                        // 税额写入为数字，缺失时留空
+                        match invoice.total_tax.to_f64() {
+                            Some(tax) => {
                                 worksheet
                                     .write_number_with_format(row, idx as u16, tax, &format)
                                     .map_err(|e| format!("写入税额失败: {}", e))?;
                             }
-                            Err(_) => {
+                            None => {
                                 worksheet
-                                    .write_string_with_format(
-                                        row,
-                                        idx as u16,
-                                        &invoice.total_tax,
-                                        &format,
-                                    )
+                                    .write_blank(row, idx as u16, &format)
                                     .map_err(|e| format!("写入税额失败: {}", e))?;
                             }
                         }
                     }
                     "价税合计" => {
-                        // 尝试将价税合计转换为数字，如果失败则保持字符串格式
-                        match invoice.total_amount_tax.parse::<f64>() {
-                            Ok(total) => {
+                        // 价税合计写入为数字，缺失时留空
+                        match invoice.total_amount_tax.to_f64() {
+                            Some(total) => {
                                 worksheet
                                     .write_number_with_format(row, idx as u16, total, &format)
                                     .map_err(|e| format!("写入价税合计失败: {}", e))?;
                             }
-                            Err(_) => {
+                            None => {
                                 worksheet
-                                    .write_string_with_format(
-                                        row,
-                                        idx as u16,
-                                        &invoice.total_amount_tax,
-                                        &format,
-                                    )
+                                    .write_blank(row, idx as u16, &format)
                                     .map_err(|e| format!("写入价税合计失败: {}", e))?;
                             }
                         }
                     }
+                    "价税合计(大写)" => {
+                        worksheet
+                            .write_string_with_format(
+                                row,
+                                idx as u16,
+                                &invoice.total_amount_tax_upper,
+                                &format,
+                            )
+                            .map_err(|e| format!("写入价税合计(大写)失败: {}", e))?;
+                    }
+                    "可抵扣" => {
+                        let deductible = if invoice.deductible { "是" } else { "否" };
+                        worksheet
+                            .write_string_with_format(row, idx as u16, deductible, &format)
+                            .map_err(|e| format!("写入可抵扣失败: {}", e))?;
+                    }
+                    "可抵扣税额" => {
+                        // 可抵扣税额写入为数字，缺失时留空
+                        match invoice.deductible_tax.to_f64() {
+                            Some(tax) => {
+                                worksheet
+                                    .write_number_with_format(row, idx as u16, tax, &format)
+                                    .map_err(|e| format!("写入可抵扣税额失败: {}", e))?;
+                            }
+                            None => {
+                                worksheet
+                                    .write_blank(row, idx as u16, &format)
+                                    .map_err(|e| format!("写入可抵扣税额失败: {}", e))?;
+                            }
+                        }
+                    }
+                    "乘车人" | "证件号" | "出发站" | "到达站" | "车次" | "席别" | "乘车日期" =>
+                    {
+                        let value = invoice
+                            .train_ticket
+                            .as_ref()
+                            .and_then(|ticket| ticket.export_value(field))
+                            .unwrap_or("");
+                        worksheet
+                            .write_string_with_format(row, idx as u16, value, &format)
+                            .map_err(|e| format!("写入{}失败: {}", field, e))?;
+                    }
+                    "旅客姓名" | "电子客票号码" | "航段" => {
+                        let value = invoice
+                            .air_itinerary
+                            .as_ref()
+                            .and_then(|itinerary| itinerary.export_value(field))
+                            .unwrap_or_default();
+                        worksheet
+                            .write_string_with_format(row, idx as u16, &value, &format)
+                            .map_err(|e| format!("写入{}失败: {}", field, e))?;
+                    }
+                    "票价" | "民航发展基金" | "燃油附加费" => {
+                        let value = invoice
+                            .air_itinerary
+                            .as_ref()
+                            .and_then(|itinerary| itinerary.export_amount(field))
+                            .or_else(|| {
+                                invoice
+                                    .train_ticket
+                                    .as_ref()
+                                    .and_then(|ticket| ticket.export_amount(field))
+                            })
+                            .unwrap_or(Money::MISSING);
+                        // 金额写入为数字，缺失时留空
+                        match value.to_f64() {
+                            Some(amount) => {
+                                worksheet
+                                    .write_number_with_format(row, idx as u16, amount, &format)
+                                    .map_err(|e| format!("写入{}失败: {}", field, e))?;
+                            }
+                            None => {
+                                worksheet
+                                    .write_blank(row, idx as u16, &format)
+                                    .map_err(|e| format!("写入{}失败: {}", field, e))?;
+                            }
+                        }
+                    }
                     "备注" => {
                         worksheet
                             .write_string_with_format(row, idx as u16, &invoice.remark, &format)
@@ -1294,11 +2026,60 @@ fn export_results(
                             )
                             .map_err(|e| format!("写入重复信息失败: {}", e))?;
                     }
+                    "校验信息" => {
+                        let messages = invoice
+                            .validation
+                            .iter()
+                            .map(|finding| finding.message())
+                            .collect::<Vec<&str>>()
+                            .join("；");
+                        worksheet
+                            .write_string_with_format(row, idx as u16, &messages, &format)
+                            .map_err(|e| format!("写入校验信息失败: {}", e))?;
+                    }
+                    "抬头校验" => {
+                        worksheet
+                            .write_string_with_format(
+                                row,
+                                idx as u16,
+                                &invoice.buyer_mismatch,
+                                &format,
+                            )
+                            .map_err(|e| format!("写入抬头校验失败: {}", e))?;
+                    }
+                    "人工修正" => {
+                        worksheet
+                            .write_string_with_format(
+                                row,
+                                idx as u16,
+                                corrections::summary(invoice),
+                                &format,
+                            )
+                            .map_err(|e| format!("写入人工修正失败: {}", e))?;
+                    }
+                    "置信度" => {
+                        worksheet
+                            .write_number_with_format(
+                                row,
+                                idx as u16,
+                                invoice.confidence.overall(),
+                                &format,
+                            )
+                            .map_err(|e| format!("写入置信度失败: {}", e))?;
+                    }
+                    "红冲信息" => {
+                        worksheet
+                            .write_string_with_format(
+                                row,
+                                idx as u16,
+                                red_letter::describe(invoice, invoices),
+                                &format,
+                            )
+                            .map_err(|e| format!("写入红冲信息失败: {}", e))?;
+                    }
                     _ => {}
                 }
             }
-
-            row += 1;
         }
     }
 
@@ -1308,7 +2089,11 @@ fn export_results(
             // 明细工作表中写入数据
             let mut row: u32 = 1;
 
-            for invoice in &processing_state.invoices {
+            for invoice in invoices {
+                if let Some(job) = job {
+                    job.check_cancelled()?;
+                }
+
                 if !invoice.items.is_empty() {
                     // 每个发票和它的商品作为一组
                     let invoice_row_start = row;
@@ -1329,14 +2114,7 @@ fn export_results(
                             .map_err(|e| format!("合并单元格失败: {}", e))?;
 
                         worksheet
-                            .merge_range(
-                                invoice_row_start,
-                                1,
-                                invoice_row_end,
-                                1,
-                                &invoice.date,
-                                &data_format,
-                            )
+                            .merge_range(invoice_row_start, 1, invoice_row_end, 1, "", &data_format)
                             .map_err(|e| format!("合并单元格失败: {}", e))?;
 
                         worksheet
@@ -1355,20 +2133,33 @@ fn export_results(
                             .write_string_with_format(
                                 row,
                                 0,
-                                &invoice.index.to_string(),
+                                invoice.index.to_string(),
                                 &data_format,
                             )
                             .map_err(|e| format!("写入序号失败: {}", e))?;
 
-                        worksheet
-                            .write_string_with_format(row, 1, &invoice.date, &data_format)
-                            .map_err(|e| format!("写入发票日期失败: {}", e))?;
-
                         worksheet
                             .write_string_with_format(row, 2, &invoice.number, &data_format)
                             .map_err(|e| format!("写入发票号码失败: {}", e))?;
                     }
 
+                    // 发票日期，能识别的日期写入为日期单元格，合并单元格时写入首行
+                    match invoice.date_value.and_then(dates::to_excel_date) {
+                        Some(date) => worksheet.write_datetime_with_format(
+                            invoice_row_start,
+                            1,
+                            &date,
+                            &date_format,
+                        ),
+                        None => worksheet.write_string_with_format(
+                            invoice_row_start,
+                            1,
+                            &invoice.date,
+                            &data_format,
+                        ),
+                    }
+                    .map_err(|e| format!("写入发票日期失败: {}", e))?;
+
                     // 写入每个商品明细
                     for (i, item) in invoice.items.iter().enumerate() {
                         let current_row = invoice_row_start + i as u32;
@@ -1378,16 +2169,24 @@ fn export_results(
                             .write_string_with_format(current_row, 3, &item.name, &data_format)
                             .map_err(|e| format!("写入项目名称失败: {}", e))?;
 
+                        // 规格型号、单位
+                        worksheet
+                            .write_string_with_format(current_row, 4, &item.spec, &data_format)
+                            .map_err(|e| format!("写入规格型号失败: {}", e))?;
+                        worksheet
+                            .write_string_with_format(current_row, 5, &item.unit, &data_format)
+                            .map_err(|e| format!("写入单位失败: {}", e))?;
+
                         // 尝试将数量转换为数字类型
                         if let Ok(quantity) = item.quantity.parse::<f64>() {
                             worksheet
-                                .write_number_with_format(current_row, 4, quantity, &data_format)
+                                .write_number_with_format(current_row, 6, quantity, &data_format)
                                 .map_err(|e| format!("写入数量失败: {}", e))?;
                         } else {
                             worksheet
                                 .write_string_with_format(
                                     current_row,
-                                    4,
+                                    6,
                                     &item.quantity,
                                     &data_format,
                                 )
@@ -1397,43 +2196,38 @@ fn export_results(
                         // 尝试将单价转换为数字类型
                         if let Ok(price) = item.price.parse::<f64>() {
                             worksheet
-                                .write_number_with_format(current_row, 5, price, &data_format)
+                                .write_number_with_format(current_row, 7, price, &data_format)
                                 .map_err(|e| format!("写入单价失败: {}", e))?;
                         } else {
                             worksheet
-                                .write_string_with_format(current_row, 5, &item.price, &data_format)
+                                .write_string_with_format(current_row, 7, &item.price, &data_format)
                                 .map_err(|e| format!("写入单价失败: {}", e))?;
                         }
 
-                        // 尝试将金额转换为数字类型
-                        if let Ok(amount) = item.amount.parse::<f64>() {
+                        // 金额写入为数字，缺失时留空
+                        if let Some(amount) = item.amount.to_f64() {
                             worksheet
-                                .write_number_with_format(current_row, 6, amount, &data_format)
+                                .write_number_with_format(current_row, 8, amount, &data_format)
                                 .map_err(|e| format!("写入金额失败: {}", e))?;
                         } else {
                             worksheet
-                                .write_string_with_format(
-                                    current_row,
-                                    6,
-                                    &item.amount,
-                                    &data_format,
-                                )
+                                .write_blank(current_row, 8, &data_format)
                                 .map_err(|e| format!("写入金额失败: {}", e))?;
                         }
 
                         // 税率
                         worksheet
-                            .write_string_with_format(current_row, 7, &item.tax_rate, &data_format)
+                            .write_string_with_format(current_row, 9, &item.tax_rate, &data_format)
                             .map_err(|e| format!("写入税率失败: {}", e))?;
 
-                        // 尝试将税额转换为数字类型
-                        if let Ok(tax) = item.tax.parse::<f64>() {
+                        // 税额写入为数字，缺失时留空
+                        if let Some(tax) = item.tax.to_f64() {
                             worksheet
-                                .write_number_with_format(current_row, 8, tax, &data_format)
+                                .write_number_with_format(current_row, 10, tax, &data_format)
                                 .map_err(|e| format!("写入税额失败: {}", e))?;
                         } else {
                             worksheet
-                                .write_string_with_format(current_row, 8, &item.tax, &data_format)
+                                .write_blank(current_row, 10, &data_format)
                                 .map_err(|e| format!("写入税额失败: {}", e))?;
                         }
                     }
@@ -1445,110 +2239,243 @@ fn export_results(
         }
     }
 
+    // 存在人工修正时，添加人工修正工作表
+    let correction_entries = corrections::collect_corrections(invoices);
+    if !correction_entries.is_empty() {
+        let worksheet = workbook.add_worksheet();
+        worksheet
+            .set_name("人工修正")
+            .map_err(|e| format!("设置人工修正工作表名称失败: {}", e))?;
+
+        let headers = [
+            ("序号", 10),
+            ("文件名", 30),
+            ("发票号码", 20),
+            ("明细行", 10),
+            ("字段", 15),
+            ("原值", 30),
+            ("修正值", 30),
+            ("修正时间", 20),
+            ("修正人", 15),
+        ];
+        for (col, (header, width)) in headers.iter().enumerate() {
+            worksheet
+                .set_column_width(col as u16, *width)
+                .map_err(|e| format!("设置人工修正列宽失败: {}", e))?;
+            worksheet
+                .write_string_with_format(0, col as u16, *header, &header_format)
+                .map_err(|e| format!("写入人工修正表头失败: {}", e))?;
+        }
+
+        for (row, entry) in correction_entries.iter().enumerate() {
+            for (col, value) in entry.export_row().iter().enumerate() {
+                worksheet
+                    .write_string_with_format(row as u32 + 1, col as u16, value, &data_format)
+                    .map_err(|e| format!("写入人工修正失败: {}", e))?;
+            }
+        }
+    }
+
     // 保存Excel文件，使用提供的文件名
     let excel_path = output_path.join(format!("{}.xlsx", file_name));
     workbook
         .save(&excel_path)
         .map_err(|e| format!("保存Excel文件失败: {}", e))?;
 
-    // 打开Excel文件
-    APP.get()
-        .unwrap()
-        .opener()
-        .open_path(excel_path.to_str().unwrap(), Option::<String>::None)
-        .map_err(|e| format!("打开Excel文件失败: {}", e))?;
-
-    Ok(())
+    Ok(excel_path)
 }
 
-#[tauri::command]
-fn set_invoices(invoices: Vec<Invoice>, state: State<AppState>) -> Result<(), String> {
-    let mut processing_state = state
-        .lock()
-        .map_err(|_| "Failed to lock state".to_string())?;
-
-    processing_state.invoices = invoices;
-
-    // 更新统计状态
-    let mut total_amount = 0.0;
-    let mut total_tax = 0.0;
+// 统计发票数量与金额
+fn compute_stats(invoices: &[Invoice]) -> ProcessingStats {
+    // 以分为单位累加，避免浮点误差
+    let mut total_amount: i64 = 0;
+    let mut total_tax: i64 = 0;
+    let mut total_amount_tax: i64 = 0;
     let mut success_count = 0;
     let mut duplicate_count = 0;
+    let mut suspected_duplicate_count = 0;
     let mut fail_count = 0;
+    let mut review_count = 0;
+    let mut buyer_mismatch_count = 0;
+    let mut red_letter_count = 0;
+    let mut by_tax_rate = std::collections::BTreeMap::new();
+    let mut by_invoice_type = std::collections::BTreeMap::new();
 
-    for invoice in &processing_state.invoices {
+    for invoice in invoices {
         match invoice.status.as_str() {
             "正常" => {
                 success_count += 1;
-                if let Ok(amount) = invoice.total_amount.parse::<f64>() {
-                    total_amount += amount;
+                if invoice.red_letter.is_some() {
+                    red_letter_count += 1;
                 }
-                if let Ok(tax) = invoice.total_tax.parse::<f64>() {
-                    total_tax += tax;
+                total_amount += invoice.total_amount.cents().unwrap_or(0);
+                total_tax += invoice.total_tax.cents().unwrap_or(0);
+                total_amount_tax += invoice.total_amount_tax.cents().unwrap_or(0);
+
+                add_to_breakdown(
+                    &mut by_invoice_type,
+                    &invoice.invoice_type,
+                    invoice.total_amount,
+                    invoice.total_tax,
+                    invoice.total_amount_tax,
+                );
+                for item in &invoice.items {
+                    let amount_tax = Money::from_cents(
+                        item.amount.cents().unwrap_or(0) + item.tax.cents().unwrap_or(0),
+                    );
+                    add_to_breakdown(
+                        &mut by_tax_rate,
+                        &item.tax_rate,
+                        item.amount,
+                        item.tax,
+                        amount_tax,
+                    );
                 }
             }
-            "重复" => duplicate_count += 1,
+            duplicate::DUPLICATE_STATUS => duplicate_count += 1,
+            duplicate::SUSPECTED_DUPLICATE_STATUS => suspected_duplicate_count += 1,
             "解析失败" => fail_count += 1,
+            validation::REVIEW_STATUS => review_count += 1,
+            buyer_whitelist::BUYER_MISMATCH_STATUS => buyer_mismatch_count += 1,
             _ => {}
         }
     }
 
-    processing_state.stats.invoice_count = processing_state.invoices.len();
-    processing_state.stats.success_count = success_count;
-    processing_state.stats.duplicate_count = duplicate_count;
-    processing_state.stats.fail_count = fail_count;
-    processing_state.stats.total_amount = format!("{:.2}", total_amount);
-    processing_state.stats.total_tax = format!("{:.2}", total_tax);
-
-    Ok(())
+    ProcessingStats {
+        total_amount: Money::from_cents(total_amount),
+        total_tax: Money::from_cents(total_tax),
+        total_amount_tax: Money::from_cents(total_amount_tax),
+        invoice_count: invoices.len(),
+        duplicate_count,
+        suspected_duplicate_count,
+        success_count,
+        fail_count,
+        review_count,
+        buyer_mismatch_count,
+        by_tax_rate: breakdown_list(by_tax_rate),
+        by_invoice_type: breakdown_list(by_invoice_type),
+        red_letter_count,
+    }
 }
 
-// 读取文件内容返回字节数组
-#[tauri::command]
-fn read_file_to_bytes(path: &str) -> Result<Vec<u8>, String> {
-    let bytes = fs::read(path).map_err(|e| format!("无法读取文件: {}", e))?;
-    Ok(bytes)
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_item(text: &str, x: f64, y: f64, width: f64) -> TextItem {
+        TextItem {
+            text: text.to_string(),
+            x,
+            y,
+            width,
+            height: 8.0,
+            page_index: 0,
+            font_name: None,
+        }
+    }
 
-// 打开PDF文件
-#[tauri::command]
-async fn open_pdf_file(path: &str) -> Result<(), String> {
-    APP.get()
-        .unwrap()
-        .opener()
-        .open_path(path, Option::<String>::None)
-        .map_err(|e| format!("打开PDF文件失败: {}", e))
-}
+    // 表头"税率/征收率"拆成两段，第二行规格型号和单位为空
+    fn item_table() -> Vec<TextItem> {
+        vec![
+            text_item("货物或应税劳务、服务名称", 20.0, 100.0, 90.0),
+            text_item("规格型号", 120.0, 100.0, 40.0),
+            text_item("单位", 170.0, 100.0, 20.0),
+            text_item("数量", 200.0, 100.0, 20.0),
+            text_item("单价", 240.0, 100.0, 20.0),
+            text_item("金额", 290.0, 100.0, 20.0),
+            text_item("税率/", 330.0, 100.0, 15.0),
+            text_item("征收率", 345.0, 100.0, 25.0),
+            text_item("税额", 390.0, 100.0, 20.0),
+            text_item("*纸制品*打印纸", 20.0, 115.0, 60.0),
+            text_item("A4", 130.0, 115.0, 10.0),
+            text_item("箱", 175.0, 115.0, 8.0),
+            text_item("2", 208.0, 115.0, 5.0),
+            text_item("100", 242.0, 115.0, 15.0),
+            text_item("200.00", 285.0, 115.0, 25.0),
+            text_item("13%", 340.0, 115.0, 15.0),
+            text_item("26.00", 388.0, 115.0, 22.0),
+            text_item("（70g）", 20.0, 123.0, 30.0),
+            text_item("*文具*签字笔", 20.0, 135.0, 50.0),
+            text_item("10", 206.0, 135.0, 10.0),
+            text_item("2", 245.0, 135.0, 5.0),
+            text_item("20.00", 287.0, 135.0, 22.0),
+            text_item("13%", 340.0, 135.0, 15.0),
+            text_item("2.60", 390.0, 135.0, 18.0),
+            text_item("合计", 20.0, 160.0, 20.0),
+        ]
+    }
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    let processing_state: AppState = Arc::new(Mutex::new(ProcessingState::default()));
-
-    tauri::Builder::default()
-        .plugin(tauri_plugin_updater::Builder::new().build())
-        .plugin(tauri_plugin_notification::init())
-        .plugin(tauri_plugin_dialog::init())
-        .plugin(tauri_plugin_opener::init())
-        .setup(|app| {
-
-            // 在应用启动时初始化 APP
-            APP.set(app.handle().clone()).unwrap();
-            
-            let handle = app.handle().clone();
-            tauri::async_runtime::spawn(async move {
-              update(handle).await.unwrap();
-            });
-            Ok(())
-        })
-        .manage(processing_state)
-        .invoke_handler(tauri::generate_handler![
-            select_output_path,
-            export_results,
-            set_invoices,
-            read_file_to_bytes,
-            parse_invoice_text,
-            open_pdf_file
-        ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+    #[test]
+    fn header_columns_join_split_labels() {
+        let items = item_table();
+        let header_row: Vec<&TextItem> = items.iter().filter(|item| item.y == 100.0).collect();
+        let columns: Vec<ItemColumn> = item_header_columns(&header_row)
+            .into_iter()
+            .map(|(column, _, _)| column)
+            .collect();
+        assert_eq!(
+            columns,
+            vec![
+                ItemColumn::Name,
+                ItemColumn::Spec,
+                ItemColumn::Unit,
+                ItemColumn::Quantity,
+                ItemColumn::Price,
+                ItemColumn::Amount,
+                ItemColumn::TaxRate,
+                ItemColumn::Tax,
+            ]
+        );
+    }
+
+    #[test]
+    fn items_assigned_by_header_columns() {
+        let mut invoice = create_empty_invoice("a.pdf", "正常", 0);
+        extract_invoice_items(&item_table(), &mut invoice);
+
+        assert_eq!(invoice.items.len(), 2);
+        let first = &invoice.items[0];
+        assert_eq!(first.name, "*纸制品*打印纸（70g）");
+        assert_eq!(first.spec, "A4");
+        assert_eq!(first.unit, "箱");
+        assert_eq!(first.quantity, "2");
+        assert_eq!(first.price, "100");
+        assert_eq!(first.amount, Money::parse("200.00"));
+        assert_eq!(first.tax_rate, "13%");
+        assert_eq!(first.tax, Money::parse("26.00"));
+
+        // 空白的规格型号和单位列不影响后续各列
+        let second = &invoice.items[1];
+        assert_eq!(second.name, "*文具*签字笔");
+        assert_eq!(second.spec, "");
+        assert_eq!(second.unit, "");
+        assert_eq!(second.quantity, "10");
+        assert_eq!(second.price, "2");
+        assert_eq!(second.amount, Money::parse("20.00"));
+        assert_eq!(second.tax_rate, "13%");
+        assert_eq!(second.tax, Money::parse("2.60"));
+    }
+
+    #[test]
+    fn total_amounts_accept_one_minus_sign() {
+        let total_row = |amount: &str, tax: &str| {
+            vec![
+                text_item("合计", 20.0, 160.0, 20.0),
+                text_item(amount, 280.0, 160.0, 30.0),
+                text_item(tax, 380.0, 160.0, 30.0),
+            ]
+        };
+
+        let mut invoice = create_empty_invoice("a.pdf", "正常", 0);
+        extract_total_amount_and_tax(&total_row("-¥100.00", "¥-13.00"), &mut invoice);
+        assert_eq!(invoice.total_amount, Money::parse("-100.00"));
+        assert_eq!(invoice.total_tax, Money::parse("-13.00"));
+
+        // 重复的负号不是金额，该单元格缺失，税额仍取自第二个单元格
+        let mut invoice = create_empty_invoice("a.pdf", "正常", 0);
+        extract_total_amount_and_tax(&total_row("-¥-100.00", "¥13.00"), &mut invoice);
+        assert!(invoice.total_amount.is_missing());
+        assert_eq!(invoice.total_tax, Money::parse("13.00"));
+        assert!(!invoice.sources.contains_key("total_amount"));
+    }
 }
@@ -0,0 +1,292 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, sync::OnceLock};
+
+// 金额，以分为单位的定点数。None 表示票面缺失或无法识别，与金额为 0 区分
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Money(Option<i64>);
+
+// 金额文本的正则，每个金额单元格都要解析，只编译一次
+static AMOUNT_REGEX: OnceLock<regex::Regex> = OnceLock::new();
+
+impl Money {
+    pub(crate) const MISSING: Money = Money(None);
+
+    pub(crate) fn from_cents(cents: i64) -> Self {
+        Money(Some(cents))
+    }
+
+    // 解析金额文本，如"¥1,234.5" -> 123450 分，无法识别时为缺失
+    pub(crate) fn parse(text: &str) -> Self {
+        let cleaned: String = text
+            .chars()
+            .filter(|c| !matches!(c, '¥' | '￥' | ',' | ' '))
+            .collect();
+        let amount_regex =
+            AMOUNT_REGEX.get_or_init(|| regex::Regex::new(r"^(-?)(\d+)(?:\.(\d{1,2}))?$").unwrap());
+        let caps = match amount_regex.captures(&cleaned) {
+            Some(caps) => caps,
+            None => return Money::MISSING,
+        };
+
+        let yuan: i64 = match caps[2].parse() {
+            Ok(yuan) => yuan,
+            Err(_) => return Money::MISSING,
+        };
+        let cents: i64 = match caps.get(3) {
+            Some(fraction) if fraction.as_str().len() == 1 => {
+                fraction.as_str().parse::<i64>().unwrap_or(0) * 10
+            }
+            Some(fraction) => fraction.as_str().parse().unwrap_or(0),
+            None => 0,
+        };
+        // 超出范围时视为无法识别
+        match yuan
+            .checked_mul(100)
+            .and_then(|value| value.checked_add(cents))
+        {
+            Some(value) => Money(Some(if &caps[1] == "-" { -value } else { value })),
+            None => Money::MISSING,
+        }
+    }
+
+    // 解析中文大写金额，如"壹仟贰佰叁拾肆圆伍角陆分" -> 123456 分，无法识别时为缺失
+    pub(crate) fn parse_uppercase(text: &str) -> Self {
+        let text = text.trim_end_matches(['整', '正']);
+        let (negative, text) = match text.strip_prefix('负') {
+            Some(rest) => (true, rest),
+            None => (false, text),
+        };
+        if text.is_empty() {
+            return Money::MISSING;
+        }
+
+        // 超出范围或无法识别时为缺失
+        Money(uppercase_cents(text).map(|value| if negative { -value } else { value }))
+    }
+
+    pub(crate) fn cents(&self) -> Option<i64> {
+        self.0
+    }
+
+    pub(crate) fn is_missing(&self) -> bool {
+        self.0.is_none()
+    }
+
+    // 写入Excel时使用的数值，仅用于展示
+    pub(crate) fn to_f64(self) -> Option<f64> {
+        self.0.map(|cents| cents as f64 / 100.0)
+    }
+}
+
+// 中文大写金额（不含"负"）对应的分，超出范围或无法识别时为 None
+fn uppercase_cents(text: &str) -> Option<i64> {
+    let digit = |c: char| "零壹贰叁肆伍陆柒捌玖".chars().position(|d| d == c);
+
+    // 圆/元之前为整数部分，之后为角分
+    let (yuan_text, fraction_text) = match text.find(['圆', '元']) {
+        Some(pos) => {
+            let unit_len = text[pos..].chars().next().unwrap().len_utf8();
+            (&text[..pos], &text[pos + unit_len..])
+        }
+        None => ("", text),
+    };
+
+    // 整数部分：拾佰仟为节内单位，万亿为节单位
+    let mut total: i64 = 0;
+    let mut section: i64 = 0;
+    let mut number: i64 = 0;
+    for c in yuan_text.chars() {
+        if let Some(d) = digit(c) {
+            number = d as i64;
+            continue;
+        }
+        let unit = match c {
+            '拾' => 10,
+            '佰' => 100,
+            '仟' => 1000,
+            '万' => {
+                section = section.checked_add(number)?.checked_mul(10_000)?;
+                number = 0;
+                continue;
+            }
+            '亿' => {
+                total = total
+                    .checked_add(section)?
+                    .checked_add(number)?
+                    .checked_mul(100_000_000)?;
+                section = 0;
+                number = 0;
+                continue;
+            }
+            _ => return None,
+        };
+        // "拾元"等省略了前面的"壹"
+        section = section.checked_add(if number == 0 { unit } else { number * unit })?;
+        number = 0;
+    }
+    let yuan = total.checked_add(section)?.checked_add(number)?;
+
+    // 小数部分：角、分
+    let mut cents: i64 = 0;
+    let mut number: i64 = 0;
+    for c in fraction_text.chars() {
+        if let Some(d) = digit(c) {
+            number = d as i64;
+            continue;
+        }
+        match c {
+            '角' => cents = cents.checked_add(number * 10)?,
+            '分' => cents = cents.checked_add(number)?,
+            _ => return None,
+        }
+        number = 0;
+    }
+
+    yuan.checked_mul(100)?.checked_add(cents)
+}
+
+// 缺失时输出空字符串，否则保留两位小数，如 123450 -> "1234.50"
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(cents) => {
+                let sign = if cents < 0 { "-" } else { "" };
+                write!(f, "{}{}.{:02}", sign, cents.abs() / 100, cents.abs() % 100)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+// 与前端保持字符串格式
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+// 兼容前端传入的字符串、数字或 null
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct MoneyVisitor;
+
+        impl de::Visitor<'_> for MoneyVisitor {
+            type Value = Money;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("金额字符串或数字")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Money, E> {
+                Ok(Money::parse(value))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Money, E> {
+                Ok(Money(value.checked_mul(100)))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Money, E> {
+                Ok(Money(
+                    i64::try_from(value)
+                        .ok()
+                        .and_then(|value| value.checked_mul(100)),
+                ))
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<Money, E> {
+                Ok(Money::from_cents((value * 100.0).round() as i64))
+            }
+
+            fn visit_unit<E: de::Error>(self) -> Result<Money, E> {
+                Ok(Money::MISSING)
+            }
+
+            fn visit_none<E: de::Error>(self) -> Result<Money, E> {
+                Ok(Money::MISSING)
+            }
+        }
+
+        deserializer.deserialize_any(MoneyVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Money;
+
+    #[test]
+    fn parse_amount_text() {
+        assert_eq!(Money::parse("¥1,234.5").cents(), Some(123450));
+        assert_eq!(Money::parse("-¥100.00").cents(), Some(-10000));
+        assert_eq!(Money::parse("￥ 0.05").cents(), Some(5));
+        assert_eq!(Money::parse("88").cents(), Some(8800));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_text() {
+        // 超过两位小数不是合法金额
+        assert!(Money::parse("100.005").is_missing());
+        assert!(Money::parse("").is_missing());
+        assert!(Money::parse("abc").is_missing());
+        assert!(Money::parse("12.3.4").is_missing());
+        assert!(Money::parse("--5").is_missing());
+    }
+
+    #[test]
+    fn parse_overflow_is_missing() {
+        assert_eq!(Money::parse("92233720368547758.07").cents(), Some(i64::MAX));
+        // 元数能表示为 i64，换算为分时溢出
+        assert!(Money::parse("92233720368547758.08").is_missing());
+        assert!(Money::parse("92233720368547759").is_missing());
+        // 元数本身超出 i64
+        assert!(Money::parse("99999999999999999999").is_missing());
+    }
+
+    #[test]
+    fn parse_uppercase_amount() {
+        assert_eq!(
+            Money::parse_uppercase("壹万零伍佰圆整").cents(),
+            Some(1_050_000)
+        );
+        assert_eq!(
+            Money::parse_uppercase("壹亿贰仟万圆整").cents(),
+            Some(12_000_000_000)
+        );
+        assert_eq!(
+            Money::parse_uppercase("叁佰捌拾柒圆零伍分").cents(),
+            Some(38_705)
+        );
+        assert_eq!(Money::parse_uppercase("负壹佰圆整").cents(), Some(-10_000));
+        assert_eq!(Money::parse_uppercase("拾圆整").cents(), Some(1_000));
+        assert_eq!(Money::parse_uppercase("伍角").cents(), Some(50));
+    }
+
+    #[test]
+    fn parse_uppercase_rejects_invalid_text() {
+        assert!(Money::parse_uppercase("").is_missing());
+        assert!(Money::parse_uppercase("整").is_missing());
+        assert!(Money::parse_uppercase("负").is_missing());
+        // 缺少"圆"时只能是角分
+        assert!(Money::parse_uppercase("壹佰贰").is_missing());
+        assert!(Money::parse_uppercase("壹佰元贰毛").is_missing());
+        assert!(Money::parse_uppercase("100元").is_missing());
+    }
+
+    #[test]
+    fn parse_uppercase_overflow_is_missing() {
+        assert_eq!(
+            Money::parse_uppercase("玖仟万亿圆整"),
+            Money::from_cents(900_000_000_000_000_000)
+        );
+        assert!(Money::parse_uppercase("壹亿亿亿圆整").is_missing());
+        assert!(Money::parse_uppercase(&format!("壹{}圆", "万".repeat(40))).is_missing());
+        assert!(Money::parse_uppercase("负玖万亿亿圆整").is_missing());
+    }
+
+    #[test]
+    fn display_keeps_two_decimals() {
+        assert_eq!(Money::from_cents(123450).to_string(), "1234.50");
+        assert_eq!(Money::from_cents(-5).to_string(), "-0.05");
+        assert_eq!(Money::MISSING.to_string(), "");
+    }
+}
@@ -0,0 +1,268 @@
+use crate::{
+    check_invoice, page_text, provenance::FieldSources, red_letter, Invoice, InvoiceItem, TextItem,
+};
+
+// 页面与前一张发票的关系
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PageRole {
+    // 新的一张发票
+    Invoice,
+    // 同一张发票的后续页，如"共2页 第2页"或发票号码与前一页相同
+    Continuation,
+    // 销售货物或者提供应税劳务清单
+    DetailList,
+}
+
+// 是否为销货清单页，发票页中的"详见销货清单"不算
+fn is_detail_list(text: &str) -> bool {
+    regex::Regex::new(r"销售货物或者?提供应税劳务\S{0,4}清单")
+        .unwrap()
+        .is_match(text)
+}
+
+// 页面上标注的页码，如"共3页 第2页"、"第2页/共3页"
+fn marked_page_number(text: &str) -> Option<usize> {
+    let total_first = regex::Regex::new(r"共(\d+)页第(\d+)页").unwrap();
+    let page_first = regex::Regex::new(r"第(\d+)页/?共(\d+)页").unwrap();
+    total_first
+        .captures(text)
+        .and_then(|caps| caps[2].parse().ok())
+        .or_else(|| {
+            page_first
+                .captures(text)
+                .and_then(|caps| caps[1].parse().ok())
+        })
+}
+
+fn page_role(text_items: &[TextItem], page: &Invoice, previous: &Invoice) -> PageRole {
+    let text = page_text(text_items);
+    if is_detail_list(&text) {
+        return PageRole::DetailList;
+    }
+    if matches!(marked_page_number(&text), Some(number) if number > 1) {
+        return PageRole::Continuation;
+    }
+    if !page.number.is_empty() && page.number == previous.number && page.code == previous.code {
+        return PageRole::Continuation;
+    }
+    PageRole::Invoice
+}
+
+// 没有识别到商品时添加的占位项，或发票页上指向销货清单的"详见销货清单"项
+fn is_placeholder_item(item: &InvoiceItem) -> bool {
+    item.name.starts_with("未能识别") || (item.name.contains("详见") && item.name.contains("清单"))
+}
+
+// 取出各明细行的来源区域，键为行内字段名，如 "amount"
+fn take_item_sources(sources: &mut FieldSources, item_count: usize) -> Vec<FieldSources> {
+    let key_regex = regex::Regex::new(r"^items\[(\d+)\]\.(.+)$").unwrap();
+    let mut rows = vec![FieldSources::new(); item_count];
+    let keys: Vec<String> = sources
+        .keys()
+        .filter(|key| key_regex.is_match(key))
+        .cloned()
+        .collect();
+    for key in keys {
+        let source = match sources.remove(&key) {
+            Some(source) => source,
+            None => continue,
+        };
+        let caps = key_regex.captures(&key).unwrap();
+        if let Some(row) = caps[1].parse::<usize>().ok().and_then(|i| rows.get_mut(i)) {
+            row.insert(caps[2].to_string(), source);
+        }
+    }
+    rows
+}
+
+// 将后续页或销货清单页的明细、合计并入发票
+fn merge_page(invoice: &mut Invoice, mut page: Invoice, role: PageRole) {
+    // 合并明细行，去掉占位项并重新编号来源区域
+    let invoice_rows = take_item_sources(&mut invoice.sources, invoice.items.len());
+    let page_rows = take_item_sources(&mut page.sources, page.items.len());
+    let rows: Vec<(InvoiceItem, FieldSources)> = invoice
+        .items
+        .drain(..)
+        .zip(invoice_rows)
+        .chain(page.items.drain(..).zip(page_rows))
+        .filter(|(item, _)| !is_placeholder_item(item))
+        .collect();
+    for (i, (item, sources)) in rows.into_iter().enumerate() {
+        for (field, source) in sources {
+            invoice
+                .sources
+                .insert(format!("items[{}].{}", i, field), source);
+        }
+        invoice.items.push(item);
+    }
+
+    // 合计取自印有价税合计的页面：多页发票为最后一页，销货清单以发票页为准
+    let take_totals = !page.total_amount_tax.is_missing()
+        && (role == PageRole::Continuation || invoice.total_amount_tax.is_missing());
+    if take_totals {
+        invoice.total_amount = page.total_amount;
+        invoice.total_tax = page.total_tax;
+        invoice.total_amount_tax = page.total_amount_tax;
+        invoice.total_amount_tax_upper = page.total_amount_tax_upper.clone();
+        invoice.total_amount_tax_upper_value = page.total_amount_tax_upper_value;
+        invoice.deductible_tax = page.deductible_tax;
+        for field in [
+            "total_amount",
+            "total_tax",
+            "total_amount_tax",
+            "total_amount_tax_upper",
+        ] {
+            match page.sources.get(field) {
+                Some(source) => invoice.sources.insert(field.to_string(), source.clone()),
+                None => invoice.sources.remove(field),
+            };
+        }
+    }
+
+    // 补充第一页没有识别到的字段
+    if invoice.red_letter.is_none() {
+        invoice.red_letter = page.red_letter.clone();
+    }
+    for (target, value, field) in [
+        (&mut invoice.code, &page.code, "code"),
+        (&mut invoice.number, &page.number, "number"),
+        (&mut invoice.date, &page.date, "date"),
+        (&mut invoice.checksum, &page.checksum, "checksum"),
+        (
+            &mut invoice.machine_number,
+            &page.machine_number,
+            "machine_number",
+        ),
+        (&mut invoice.remark, &page.remark, "remark"),
+        (&mut invoice.payee, &page.payee, "payee"),
+        (&mut invoice.reviewer, &page.reviewer, "reviewer"),
+        (&mut invoice.drawer, &page.drawer, "drawer"),
+        (&mut invoice.buyer.name, &page.buyer.name, "buyer.name"),
+        (
+            &mut invoice.buyer.tax_code,
+            &page.buyer.tax_code,
+            "buyer.tax_code",
+        ),
+        (&mut invoice.seller.name, &page.seller.name, "seller.name"),
+        (
+            &mut invoice.seller.tax_code,
+            &page.seller.tax_code,
+            "seller.tax_code",
+        ),
+    ] {
+        if target.is_empty() && !value.is_empty() {
+            *target = value.clone();
+            if let Some(source) = page.sources.get(field) {
+                invoice.sources.insert(field.to_string(), source.clone());
+            }
+        }
+    }
+}
+
+// 将同一张发票的后续页和销货清单并入该发票的第一页，pages 与各页解析结果一一对应
+// 第一页解析失败时不合并，后续页仍作为独立的发票
+pub(crate) fn merge_pages(invoices: Vec<Invoice>, pages: &[Vec<TextItem>]) -> Vec<Invoice> {
+    let mut merged: Vec<Invoice> = Vec::new();
+    let mut changed: Vec<bool> = Vec::new();
+    for (page, text_items) in invoices.into_iter().zip(pages) {
+        let role = match merged.last() {
+            Some(previous) if previous.status != "解析失败" && !text_items.is_empty() => {
+                page_role(text_items, &page, previous)
+            }
+            _ => PageRole::Invoice,
+        };
+        match (role, merged.last_mut()) {
+            (PageRole::Invoice, _) | (_, None) => {
+                merged.push(page);
+                changed.push(false);
+            }
+            (_, Some(invoice)) => {
+                merge_page(invoice, page, role);
+                if let Some(flag) = changed.last_mut() {
+                    *flag = true;
+                }
+            }
+        }
+    }
+
+    // 后续页可能没有红字标记，合并后重新统一红字发票的金额符号并校验金额
+    for (invoice, changed) in merged.iter_mut().zip(changed) {
+        if changed {
+            if invoice.red_letter.is_some() {
+                red_letter::negate_amounts(invoice);
+            }
+            check_invoice(invoice);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{create_empty_invoice, money::Money};
+
+    fn text_item(text: &str) -> TextItem {
+        TextItem {
+            text: text.to_string(),
+            x: 0.0,
+            y: 0.0,
+            width: 0.0,
+            height: 0.0,
+            page_index: 0,
+            font_name: None,
+        }
+    }
+
+    fn item(name: &str, amount: &str, tax: &str) -> InvoiceItem {
+        InvoiceItem {
+            name: name.to_string(),
+            spec: "".to_string(),
+            unit: "".to_string(),
+            quantity: "1".to_string(),
+            price: "".to_string(),
+            amount: Money::parse(amount),
+            tax_rate: "13%".to_string(),
+            tax: Money::parse(tax),
+        }
+    }
+
+    // 第一页识别为红字发票，第二页没有红字标记，金额和合计均为正数
+    #[test]
+    fn red_letter_continuation_is_negated() {
+        let mut first = create_empty_invoice("red.pdf", "正常", 0);
+        first.number = "24442000000012345678".to_string();
+        first.items = vec![item("*纸制品*打印纸", "-100.00", "-13.00")];
+        red_letter::detect(&mut first, &[text_item("销项负数")]);
+        assert!(first.red_letter.is_some());
+
+        let mut second = create_empty_invoice("red.pdf", "正常", 1);
+        second.number = first.number.clone();
+        second.items = vec![item("*文具*签字笔", "50.00", "6.50")];
+        second.total_amount = Money::parse("150.00");
+        second.total_tax = Money::parse("19.50");
+        second.total_amount_tax = Money::parse("169.50");
+        second.total_amount_tax_upper_value = Money::parse("169.50");
+
+        let pages = vec![vec![text_item("共2页第1页")], vec![text_item("共2页第2页")]];
+        let merged = merge_pages(vec![first, second], &pages);
+
+        assert_eq!(merged.len(), 1);
+        let invoice = &merged[0];
+        assert!(invoice.red_letter.is_some());
+        assert_eq!(invoice.total_amount, Money::parse("-150.00"));
+        assert_eq!(invoice.total_tax, Money::parse("-19.50"));
+        assert_eq!(invoice.total_amount_tax, Money::parse("-169.50"));
+        assert_eq!(
+            invoice.total_amount_tax_upper_value,
+            Money::parse("-169.50")
+        );
+        let amounts: Vec<Money> = invoice.items.iter().map(|item| item.amount).collect();
+        assert_eq!(
+            amounts,
+            vec![Money::parse("-100.00"), Money::parse("-50.00")]
+        );
+        let taxes: Vec<Money> = invoice.items.iter().map(|item| item.tax).collect();
+        assert_eq!(taxes, vec![Money::parse("-13.00"), Money::parse("-6.50")]);
+    }
+}
@@ -0,0 +1,170 @@
+use pdf_extract::{Document, MediaBox, OutputDev, OutputError, Transform};
+use std::path::Path;
+
+use crate::TextItem;
+
+// 单个字符的位置信息（已转换为与 pdf.js viewport 一致的坐标系：原点在左上角，y 向下）
+struct PositionedChar {
+    text: String,
+    x: f64,
+    y: f64,
+    advance: f64,
+    height: f64,
+}
+
+// 收集每一页的文本项，对应前端 pdfParser.ts 中 getTextContent 的处理结果
+struct TextItemCollector {
+    pages: Vec<Vec<TextItem>>,
+    current_page: Vec<TextItem>,
+    page_index: usize,
+    media_box: (f64, f64),
+    word: Vec<PositionedChar>,
+}
+
+impl TextItemCollector {
+    fn new() -> Self {
+        Self {
+            pages: Vec::new(),
+            current_page: Vec::new(),
+            page_index: 0,
+            media_box: (0.0, 0.0),
+            word: Vec::new(),
+        }
+    }
+
+    // 将当前单词中的字符合并为文本项，遇到较大间距时拆分
+    fn flush_word(&mut self) {
+        let chars = std::mem::take(&mut self.word);
+        let mut run: Vec<&PositionedChar> = Vec::new();
+
+        for ch in &chars {
+            if let Some(last) = run.last() {
+                let gap = ch.x - (last.x + last.advance);
+                if (ch.y - last.y).abs() > 1.0 || gap > last.height.max(1.0) {
+                    self.push_run(&run);
+                    run.clear();
+                }
+            }
+            run.push(ch);
+        }
+        self.push_run(&run);
+    }
+
+    fn push_run(&mut self, run: &[&PositionedChar]) {
+        // 与前端保持一致：去除文本中的所有空白，跳过空文本
+        let text: String = run
+            .iter()
+            .flat_map(|ch| ch.text.chars())
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        if text.is_empty() {
+            return;
+        }
+
+        let first = run[0];
+        let last = run[run.len() - 1];
+        let height = run.iter().fold(0.0_f64, |h, ch| h.max(ch.height));
+        let x = first.x;
+        let y = first.y;
+        let width = (last.x + last.advance - first.x).max(0.0);
+
+        // 紧邻上一个文本项（同一基线且无间距）时合并，模拟 pdf.js 对 TJ 数组的合并
+        if let Some(prev) = self.current_page.last_mut() {
+            let prev_right = prev.x + prev.width;
+            if (prev.y - y).abs() < 0.5 && (x - prev_right).abs() < height.max(1.0) * 0.1 {
+                prev.text.push_str(&text);
+                prev.width = (x + width - prev.x).max(prev.width);
+                prev.height = prev.height.max(height);
+                return;
+            }
+        }
+
+        self.current_page.push(TextItem {
+            text,
+            x,
+            y,
+            width,
+            height,
+            page_index: self.page_index,
+            // pdf-extract 的 OutputDev 不提供字体信息
+            font_name: None,
+        });
+    }
+}
+
+impl OutputDev for TextItemCollector {
+    fn begin_page(
+        &mut self,
+        page_num: u32,
+        media_box: &MediaBox,
+        _art_box: Option<(f64, f64, f64, f64)>,
+    ) -> Result<(), OutputError> {
+        self.page_index = (page_num as usize).saturating_sub(1);
+        self.media_box = (media_box.llx, media_box.ury);
+        self.current_page = Vec::new();
+        Ok(())
+    }
+
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        self.flush_word();
+        let page = std::mem::take(&mut self.current_page);
+        self.pages.push(page);
+        Ok(())
+    }
+
+    fn output_character(
+        &mut self,
+        trm: &Transform,
+        width: f64,
+        spacing: f64,
+        font_size: f64,
+        char: &str,
+    ) -> Result<(), OutputError> {
+        // 文本空间的原点经过 Trm 变换后即为字符基线起点
+        let (llx, ury) = self.media_box;
+        let advance = width * font_size + spacing;
+        self.word.push(PositionedChar {
+            text: char.to_string(),
+            x: trm.m31 - llx,
+            y: ury - trm.m32,
+            advance: (advance * trm.m11).hypot(advance * trm.m12),
+            height: (font_size * trm.m21).hypot(font_size * trm.m22),
+        });
+        Ok(())
+    }
+
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        self.flush_word();
+        Ok(())
+    }
+
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        self.flush_word();
+        Ok(())
+    }
+
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        Ok(())
+    }
+}
+
+// 使用 pdf-extract 读取PDF，按页返回带坐标的文本项
+pub(crate) fn extract_pages_text_items(path: &Path) -> Result<Vec<Vec<TextItem>>, String> {
+    let mut doc = Document::load(path).map_err(|e| format!("无法读取PDF文件: {}", e))?;
+    let mut collector = TextItemCollector::new();
+
+    // pdf-extract 遇到不支持的字体时可能 panic，防止影响整体流程
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        if doc.is_encrypted() {
+            pdf_extract::output_doc_encrypted(&mut doc, &mut collector, "")
+        } else {
+            pdf_extract::output_doc(&doc, &mut collector)
+        }
+    }));
+
+    match result {
+        Ok(Ok(())) => Ok(collector.pages),
+        Ok(Err(e)) => Err(format!("提取PDF文本失败: {:?}", e)),
+        Err(_) => Err("提取PDF文本时发生错误".to_string()),
+    }
+}
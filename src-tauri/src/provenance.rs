@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::{group_items_by_row, Invoice, TextItem};
+
+// 字段来源区域，页码从1开始；坐标与 TextItem 一致（原点在左上角，y 向下），y 为文本顶部
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct SourceBox {
+    page: usize,
+    x: f64,
+    y: f64,
+    width: f64,
+    height: f64,
+}
+
+impl SourceBox {
+    pub(crate) fn from_item(item: &TextItem) -> Self {
+        SourceBox {
+            page: item.page_index + 1,
+            x: item.x,
+            y: item.y - item.height,
+            width: item.width,
+            height: item.height,
+        }
+    }
+}
+
+// 按版面位置（表格列、合计行、区域）提取的值的匹配程度
+pub(crate) const LAYOUT_SCORE: f64 = 0.9;
+// 解析器未记录来源、按文本查找到的值的匹配程度
+const LOCATED_SCORE: f64 = 0.5;
+
+// 字段的来源区域，label_score 为值与标签的匹配程度（0~1），用于计算置信度
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct FieldSource {
+    boxes: Vec<SourceBox>,
+    label_score: f64,
+}
+
+impl FieldSource {
+    // 用户在PDF预览中框选的来源区域
+    pub(crate) fn manual(source: SourceBox) -> Self {
+        FieldSource {
+            boxes: vec![source],
+            label_score: 1.0,
+        }
+    }
+
+    pub(crate) fn label_score(&self) -> f64 {
+        self.label_score
+    }
+}
+
+// 各字段的来源区域，键为字段路径，如 "number"、"seller.name"、"items[0].amount"
+pub type FieldSources = BTreeMap<String, FieldSource>;
+
+// 按标签提取的结果：值、组成该值的文本项及与标签的匹配程度
+pub(crate) struct Extracted<'a> {
+    pub(crate) value: String,
+    pub(crate) items: Vec<&'a TextItem>,
+    pub(crate) label_score: f64,
+}
+
+impl<'a> Extracted<'a> {
+    pub(crate) fn empty() -> Self {
+        Extracted {
+            value: "".to_string(),
+            items: Vec::new(),
+            label_score: 0.0,
+        }
+    }
+
+    // 对提取到的值做进一步处理，如只保留数字
+    pub(crate) fn map_value(self, f: impl FnOnce(String) -> String) -> Self {
+        Extracted {
+            value: f(self.value),
+            ..self
+        }
+    }
+}
+
+// 标签匹配程度：正则完整匹配标签文本时为1，否则为0.9；值与标签的水平间距越大得分越低，最多降低0.3
+pub(crate) fn label_score(
+    label: &TextItem,
+    pattern: &regex::Regex,
+    value_items: &[&TextItem],
+    max_distance: f64,
+) -> f64 {
+    let exact = pattern
+        .find(&label.text)
+        .map(|m| m.start() == 0 && m.end() == label.text.len())
+        .unwrap_or(false);
+    let match_score = if exact { 1.0 } else { 0.9 };
+    let distance_score = match value_items.first() {
+        Some(first) if max_distance > 0.0 => {
+            let gap = (first.x - (label.x + label.width)).max(0.0);
+            1.0 - 0.3 * (gap / max_distance).min(1.0)
+        }
+        _ => 1.0,
+    };
+    match_score * distance_score
+}
+
+// 记录字段的来源文本项，覆盖之前的记录；没有来源时不记录
+pub(crate) fn record(
+    sources: &mut FieldSources,
+    field: &str,
+    items: &[&TextItem],
+    label_score: f64,
+) {
+    if items.is_empty() {
+        return;
+    }
+    sources.insert(
+        field.to_string(),
+        FieldSource {
+            boxes: items
+                .iter()
+                .map(|item| SourceBox::from_item(item))
+                .collect(),
+            label_score,
+        },
+    );
+}
+
+// 追加字段的来源文本项，用于跨行的商品名称等
+pub(crate) fn append(sources: &mut FieldSources, field: &str, items: &[&TextItem]) {
+    if items.is_empty() {
+        return;
+    }
+    sources
+        .entry(field.to_string())
+        .or_insert_with(|| FieldSource {
+            boxes: Vec::new(),
+            label_score: LAYOUT_SCORE,
+        })
+        .boxes
+        .extend(items.iter().map(|item| SourceBox::from_item(item)));
+}
+
+// 记录提取结果的来源并返回提取到的值
+pub(crate) fn record_value(
+    sources: &mut FieldSources,
+    field: &str,
+    extracted: Extracted,
+) -> String {
+    if !extracted.value.is_empty() {
+        record(sources, field, &extracted.items, extracted.label_score);
+    }
+    extracted.value
+}
+
+// 按文本查找值所在的区域：先找内容相同或包含该值的文本项，再找同一行中拼接后等于该值的相邻文本项
+fn locate(text_items: &[TextItem], value: &str) -> Vec<SourceBox> {
+    let value: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    if value.is_empty() {
+        return Vec::new();
+    }
+
+    // 单个字符只按完全相同匹配，避免误匹配
+    let found = text_items
+        .iter()
+        .find(|item| item.text == value)
+        .or_else(|| {
+            if value.chars().count() < 2 {
+                return None;
+            }
+            text_items.iter().find(|item| item.text.contains(&value))
+        });
+    if let Some(item) = found {
+        return vec![SourceBox::from_item(item)];
+    }
+
+    let items: Vec<&TextItem> = text_items.iter().collect();
+    for row in group_items_by_row(&items, 3.0) {
+        for start in 0..row.len() {
+            let mut text = String::new();
+            for (end, item) in row.iter().enumerate().skip(start) {
+                text.push_str(&item.text);
+                if text == value {
+                    return row[start..=end]
+                        .iter()
+                        .map(|item| SourceBox::from_item(item))
+                        .collect();
+                }
+                if !value.starts_with(&text) {
+                    break;
+                }
+            }
+        }
+    }
+
+    Vec::new()
+}
+
+// 为解析器没有记录来源的字段按文本补充来源区域，合计金额、税额可能由票价计算得出，不按文本查找
+pub(crate) fn fill_missing(invoice: &mut Invoice, text_items: &[TextItem]) {
+    let mut values: Vec<(String, String)> = [
+        ("code", invoice.code.clone()),
+        ("number", invoice.number.clone()),
+        ("date", invoice.date.clone()),
+        ("checksum", invoice.checksum.clone()),
+        ("machine_number", invoice.machine_number.clone()),
+        ("payee", invoice.payee.clone()),
+        ("reviewer", invoice.reviewer.clone()),
+        ("drawer", invoice.drawer.clone()),
+        ("buyer.name", invoice.buyer.name.clone()),
+        ("buyer.tax_code", invoice.buyer.tax_code.clone()),
+        ("buyer.address_phone", invoice.buyer.address_phone.clone()),
+        ("buyer.bank_account", invoice.buyer.bank_account.clone()),
+        ("seller.name", invoice.seller.name.clone()),
+        ("seller.tax_code", invoice.seller.tax_code.clone()),
+        ("seller.address_phone", invoice.seller.address_phone.clone()),
+        ("seller.bank_account", invoice.seller.bank_account.clone()),
+        ("total_amount_tax", invoice.total_amount_tax.to_string()),
+        (
+            "total_amount_tax_upper",
+            invoice.total_amount_tax_upper.clone(),
+        ),
+    ]
+    .into_iter()
+    .map(|(field, value)| (field.to_string(), value))
+    .collect();
+
+    if let Some(ticket) = &invoice.train_ticket {
+        values.extend(
+            ticket
+                .source_values()
+                .into_iter()
+                .map(|(field, value)| (format!("train_ticket.{}", field), value)),
+        );
+    }
+    if let Some(itinerary) = &invoice.air_itinerary {
+        values.extend(
+            itinerary
+                .source_values()
+                .into_iter()
+                .map(|(field, value)| (format!("air_itinerary.{}", field), value)),
+        );
+    }
+
+    for (field, value) in values {
+        if value.is_empty() || invoice.sources.contains_key(&field) {
+            continue;
+        }
+        let boxes = locate(text_items, &value);
+        if !boxes.is_empty() {
+            invoice.sources.insert(
+                field,
+                FieldSource {
+                    boxes,
+                    label_score: LOCATED_SCORE,
+                },
+            );
+        }
+    }
+}
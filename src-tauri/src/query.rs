@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+use crate::{dates, money::Money, Invoice};
+
+// 默认每页条数
+const DEFAULT_PAGE_SIZE: usize = 50;
+
+// 发票筛选条件，未填写的条件不参与筛选
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct InvoiceFilter {
+    // 状态，任一匹配即可
+    #[serde(default)]
+    status: Vec<String>,
+    // 开票日期范围，如 2024-05-01，包含起止日期
+    #[serde(default)]
+    date_from: String,
+    #[serde(default)]
+    date_to: String,
+    // 销售方名称或税号，包含即可
+    #[serde(default)]
+    seller: String,
+    // 价税合计范围，包含上下限
+    #[serde(default)]
+    amount_min: Money,
+    #[serde(default)]
+    amount_max: Money,
+    #[serde(default)]
+    invoice_type: String,
+}
+
+// 排序字段
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    Index,
+    Date,
+    Amount,
+    Seller,
+    Status,
+    InvoiceType,
+    Number,
+    // 按置信度升序时，低置信度的发票排在最前，便于优先复核
+    Confidence,
+}
+
+// 发票列表查询：筛选、排序和分页，页码从1开始
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct InvoiceQuery {
+    #[serde(default)]
+    filter: InvoiceFilter,
+    #[serde(default)]
+    sort_by: SortField,
+    #[serde(default)]
+    descending: bool,
+    #[serde(default)]
+    page: usize,
+    #[serde(default)]
+    page_size: usize,
+}
+
+// 一页查询结果，total 为符合筛选条件的发票总数
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InvoicePage {
+    invoices: Vec<Invoice>,
+    total: usize,
+    page: usize,
+    page_size: usize,
+}
+
+// 状态排序顺序，与前端列表一致
+fn status_order(status: &str) -> usize {
+    match status {
+        "正常" => 1,
+        "需复核" => 2,
+        "抬头不符" => 3,
+        "疑似重复" => 4,
+        "重复" => 5,
+        "解析失败" => 6,
+        _ => 7,
+    }
+}
+
+impl InvoiceFilter {
+    fn matches(&self, invoice: &Invoice) -> bool {
+        if !self.status.is_empty() && !self.status.contains(&invoice.status) {
+            return false;
+        }
+
+        if !self.date_from.is_empty() || !self.date_to.is_empty() {
+            let date = match dates::invoice_date(invoice) {
+                Some(date) => date,
+                None => return false,
+            };
+            if let Some(from) = dates::parse_date(&self.date_from) {
+                if date < from {
+                    return false;
+                }
+            }
+            if let Some(to) = dates::parse_date(&self.date_to) {
+                if date > to {
+                    return false;
+                }
+            }
+        }
+
+        let seller = self.seller.trim();
+        if !seller.is_empty()
+            && !invoice.seller.name.contains(seller)
+            && !invoice
+                .seller
+                .tax_code
+                .to_uppercase()
+                .contains(&seller.to_uppercase())
+        {
+            return false;
+        }
+
+        if !self.amount_min.is_missing() || !self.amount_max.is_missing() {
+            let amount = match invoice.total_amount_tax.cents() {
+                Some(amount) => amount,
+                None => return false,
+            };
+            if matches!(self.amount_min.cents(), Some(min) if amount < min) {
+                return false;
+            }
+            if matches!(self.amount_max.cents(), Some(max) if amount > max) {
+                return false;
+            }
+        }
+
+        if !self.invoice_type.is_empty() && invoice.invoice_type != self.invoice_type {
+            return false;
+        }
+
+        true
+    }
+}
+
+impl SortField {
+    fn compare(&self, a: &Invoice, b: &Invoice) -> Ordering {
+        match self {
+            SortField::Index => a.index.cmp(&b.index),
+            SortField::Date => dates::invoice_date(a).cmp(&dates::invoice_date(b)),
+            SortField::Amount => a.total_amount_tax.cents().cmp(&b.total_amount_tax.cents()),
+            SortField::Seller => a.seller.name.cmp(&b.seller.name),
+            SortField::Status => status_order(&a.status).cmp(&status_order(&b.status)),
+            SortField::InvoiceType => a.invoice_type.cmp(&b.invoice_type),
+            SortField::Number => a.number.cmp(&b.number),
+            SortField::Confidence => a.confidence.overall().total_cmp(&b.confidence.overall()),
+        }
+    }
+}
+
+// 按条件筛选、排序并分页，相同排序值按序号排列
+pub(crate) fn query_invoices(invoices: &[Invoice], query: &InvoiceQuery) -> InvoicePage {
+    let mut matched: Vec<&Invoice> = invoices
+        .iter()
+        .filter(|invoice| query.filter.matches(invoice))
+        .collect();
+    matched.sort_by(|a, b| {
+        let ordering = query.sort_by.compare(a, b);
+        let ordering = if query.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        ordering.then(a.index.cmp(&b.index))
+    });
+
+    let page = query.page.max(1);
+    let page_size = if query.page_size == 0 {
+        DEFAULT_PAGE_SIZE
+    } else {
+        query.page_size
+    };
+    let total = matched.len();
+
+    InvoicePage {
+        invoices: matched
+            .into_iter()
+            .skip((page - 1) * page_size)
+            .take(page_size)
+            .cloned()
+            .collect(),
+        total,
+        page,
+        page_size,
+    }
+}
@@ -0,0 +1,187 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{duplicate, ledger::Ledger, money::Money, Invoice, TextItem};
+
+// 红字发票（销项负数发票）对应的正数发票
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct RedLetter {
+    // 备注中注明的正数发票代码和号码，全电发票没有代码，未注明时为空
+    original_code: String,
+    original_number: String,
+    // 正数发票在本批次中的序号，0 表示不在本批次
+    #[serde(default)]
+    original_index: usize,
+    // 正数发票记入台账的批次，不在台账中时为空
+    #[serde(default)]
+    original_batch: String,
+}
+
+// 负数金额取反为正数，缺失或已为负数时不变
+fn negate_positive(amount: &mut Money) {
+    if let Some(cents) = amount.cents() {
+        if cents > 0 {
+            *amount = Money::from_cents(-cents);
+        }
+    }
+}
+
+// 将红字发票票面上未带负号的合计和明细金额统一为负数
+pub(crate) fn negate_amounts(invoice: &mut Invoice) {
+    negate_positive(&mut invoice.total_amount);
+    negate_positive(&mut invoice.total_tax);
+    negate_positive(&mut invoice.total_amount_tax);
+    negate_positive(&mut invoice.total_amount_tax_upper_value);
+    negate_positive(&mut invoice.deductible_tax);
+    for item in &mut invoice.items {
+        negate_positive(&mut item.amount);
+        negate_positive(&mut item.tax);
+    }
+}
+
+// 识别红字发票：标题含"红字"、注明"销项负数"、备注中注明对应的正数发票，或价税合计为负数
+// 识别为红字发票后，将票面上未带负号的金额统一为负数，便于与正数发票冲抵
+pub(crate) fn detect(invoice: &mut Invoice, text_items: &[TextItem]) {
+    let text: String = text_items
+        .iter()
+        .flat_map(|item| item.text.chars())
+        .filter(|c| !c.is_whitespace())
+        .collect();
+
+    // 纸质、电子发票："对应正数发票代码:XXX号码:YYY"；全电发票："被红冲蓝字数电票号码：YYY"
+    let paper_regex =
+        regex::Regex::new(r"对应正数发票代码[:：]?(\d{10,12})号码[:：]?(\d{8,20})").unwrap();
+    let digital_regex =
+        regex::Regex::new(r"(?:被红冲|对应)蓝字\S{0,6}?号码[:：]?(\d{8,20})").unwrap();
+    let (original_code, original_number) = if let Some(caps) = paper_regex.captures(&text) {
+        (caps[1].to_string(), caps[2].to_string())
+    } else if let Some(caps) = digital_regex.captures(&text) {
+        ("".to_string(), caps[1].to_string())
+    } else {
+        ("".to_string(), "".to_string())
+    };
+
+    let is_red_letter = invoice.title.contains("红字")
+        || text.contains("销项负数")
+        || !original_number.is_empty()
+        || matches!(invoice.total_amount_tax.cents(), Some(cents) if cents < 0);
+    if !is_red_letter {
+        return;
+    }
+
+    negate_amounts(invoice);
+    invoice.red_letter = Some(RedLetter {
+        original_code,
+        original_number,
+        ..Default::default()
+    });
+}
+
+// 是否为红字发票冲红的正数发票，全电发票或未注明代码时仅比较号码
+fn is_original(invoice: &Invoice, red_letter: &RedLetter) -> bool {
+    invoice.red_letter.is_none()
+        && !red_letter.original_number.is_empty()
+        && invoice.number == red_letter.original_number
+        && (red_letter.original_code.is_empty()
+            || invoice.code.is_empty()
+            || invoice.code == red_letter.original_code)
+}
+
+// 关联红字发票与其冲红的正数发票：先在本批次中查找，找不到时在台账中查找
+// 需在设置序号后调用，解析失败和重复的发票不参与关联
+pub(crate) fn link_originals(
+    invoices: &mut [Invoice],
+    ledger: Option<&Ledger>,
+) -> Result<(), String> {
+    for invoice in invoices.iter_mut() {
+        invoice.reversed_by.clear();
+    }
+
+    for i in 0..invoices.len() {
+        let mut red_letter = match &invoices[i].red_letter {
+            Some(red_letter) => red_letter.clone(),
+            None => continue,
+        };
+        red_letter.original_index = 0;
+        red_letter.original_batch = "".to_string();
+
+        let linkable = |invoice: &Invoice| {
+            invoice.status != "解析失败" && invoice.status != duplicate::DUPLICATE_STATUS
+        };
+        if linkable(&invoices[i]) {
+            let original = invoices
+                .iter()
+                .position(|invoice| linkable(invoice) && is_original(invoice, &red_letter));
+            match original {
+                Some(j) => {
+                    red_letter.original_index = invoices[j].index;
+                    let index = invoices[i].index;
+                    invoices[j].reversed_by.push(index);
+                }
+                None => {
+                    if let Some(ledger) = ledger {
+                        if let Some(entry) = ledger
+                            .find_original(&red_letter.original_code, &red_letter.original_number)?
+                        {
+                            red_letter.original_batch = entry.batch().to_string();
+                        }
+                    }
+                }
+            }
+        }
+
+        invoices[i].red_letter = Some(red_letter);
+    }
+    Ok(())
+}
+
+// 正数发票与冲红它的红字发票的价税合计之和，以分为单位
+fn net_amount_tax(original: &Invoice, invoices: &[Invoice]) -> i64 {
+    let reversed: i64 = invoices
+        .iter()
+        .filter(|other| original.reversed_by.contains(&other.index))
+        .filter_map(|other| other.total_amount_tax.cents())
+        .sum();
+    original.total_amount_tax.cents().unwrap_or(0) + reversed
+}
+
+// 红冲说明，用于导出，如"红字发票，冲红第1个发票 12345678，冲红后价税合计 0.00"
+pub(crate) fn describe(invoice: &Invoice, invoices: &[Invoice]) -> String {
+    if let Some(red_letter) = &invoice.red_letter {
+        let original = format!("{}{}", red_letter.original_code, red_letter.original_number);
+        let original_invoice = invoices.iter().find(|other| {
+            red_letter.original_index > 0 && other.index == red_letter.original_index
+        });
+        return if let Some(original_invoice) = original_invoice {
+            format!(
+                "红字发票，冲红第{}个发票 {}，冲红后价税合计 {}",
+                red_letter.original_index,
+                original,
+                Money::from_cents(net_amount_tax(original_invoice, invoices))
+            )
+        } else if !red_letter.original_batch.is_empty() {
+            format!(
+                "红字发票，冲红台账中 {} 批次导入的发票 {}",
+                red_letter.original_batch, original
+            )
+        } else if !original.is_empty() {
+            format!("红字发票，对应正数发票 {} 不在本批次和台账中", original)
+        } else {
+            "红字发票，未注明对应的正数发票".to_string()
+        };
+    }
+
+    if invoice.reversed_by.is_empty() {
+        return "".to_string();
+    }
+    let indexes = invoice
+        .reversed_by
+        .iter()
+        .map(|index| format!("第{}个", index))
+        .collect::<Vec<String>>()
+        .join("、");
+    format!(
+        "已被{}发票冲红，冲红后价税合计 {}",
+        indexes,
+        Money::from_cents(net_amount_tax(invoice, invoices))
+    )
+}
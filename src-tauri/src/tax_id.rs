@@ -0,0 +1,170 @@
+// 纳税人识别号校验结果
+pub(crate) const TAX_CODE_VALID: &str = "有效";
+pub(crate) const TAX_CODE_BAD_CHECKSUM: &str = "校验位错误";
+pub(crate) const TAX_CODE_BAD_FORMAT: &str = "格式错误";
+
+// 统一社会信用代码字符集（GB 32100，不使用 I、O、Z、S、V）
+const CREDIT_CODE_CHARSET: &str = "0123456789ABCDEFGHJKLMNPQRTUWXY";
+const CREDIT_CODE_WEIGHTS: [u32; 17] = [
+    1, 3, 9, 27, 19, 26, 16, 17, 20, 29, 25, 13, 8, 24, 10, 30, 28,
+];
+
+// 组织机构代码（GB 11714）加权因子
+const ORGANIZATION_CODE_WEIGHTS: [u32; 8] = [3, 7, 9, 10, 5, 8, 4, 2];
+
+// 居民身份证号码（GB 11643）加权因子与校验码
+const ID_CARD_WEIGHTS: [u32; 17] = [7, 9, 10, 5, 8, 4, 2, 1, 6, 3, 7, 9, 10, 5, 8, 4, 2];
+const ID_CARD_CHECK_CHARS: &str = "10X98765432";
+
+// 校验18位统一社会信用代码：第3-8位为行政区划码，第18位为校验码
+fn check_credit_code(code: &str) -> &'static str {
+    let values: Option<Vec<u32>> = code
+        .chars()
+        .map(|c| CREDIT_CODE_CHARSET.find(c).map(|pos| pos as u32))
+        .collect();
+    let values = match values {
+        Some(values) => values,
+        None => return TAX_CODE_BAD_FORMAT,
+    };
+    if !code[2..8].chars().all(|c| c.is_ascii_digit()) {
+        return TAX_CODE_BAD_FORMAT;
+    }
+
+    let sum: u32 = values[..17]
+        .iter()
+        .zip(CREDIT_CODE_WEIGHTS.iter())
+        .map(|(value, weight)| value * weight)
+        .sum();
+    let check = (31 - sum % 31) % 31;
+    if values[17] == check {
+        TAX_CODE_VALID
+    } else {
+        TAX_CODE_BAD_CHECKSUM
+    }
+}
+
+// 校验9位组织机构代码，校验码为 0-9 或 X
+fn check_organization_code(code: &str) -> &'static str {
+    let chars: Vec<char> = code.chars().collect();
+    if !chars[..8]
+        .iter()
+        .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase())
+    {
+        return TAX_CODE_BAD_FORMAT;
+    }
+
+    let sum: u32 = chars[..8]
+        .iter()
+        .zip(ORGANIZATION_CODE_WEIGHTS.iter())
+        .map(|(c, weight)| c.to_digit(36).unwrap() * weight)
+        .sum();
+    let expected = match 11 - sum % 11 {
+        10 => 'X',
+        11 => '0',
+        check => char::from_digit(check, 10).unwrap(),
+    };
+    if chars[8] == expected {
+        TAX_CODE_VALID
+    } else {
+        TAX_CODE_BAD_CHECKSUM
+    }
+}
+
+// 校验18位居民身份证号码
+fn check_id_card(code: &str) -> &'static str {
+    let chars: Vec<char> = code.chars().collect();
+    if !chars[..17].iter().all(|c| c.is_ascii_digit())
+        || !(chars[17].is_ascii_digit() || chars[17] == 'X')
+    {
+        return TAX_CODE_BAD_FORMAT;
+    }
+
+    let sum: u32 = chars[..17]
+        .iter()
+        .zip(ID_CARD_WEIGHTS.iter())
+        .map(|(c, weight)| c.to_digit(10).unwrap() * weight)
+        .sum();
+    let expected = ID_CARD_CHECK_CHARS
+        .chars()
+        .nth((sum % 11) as usize)
+        .unwrap();
+    if chars[17] == expected {
+        TAX_CODE_VALID
+    } else {
+        TAX_CODE_BAD_CHECKSUM
+    }
+}
+
+// 校验纳税人识别号，未填写时返回空字符串
+// 18位：统一社会信用代码；15位：旧版税号，6位行政区划码 + 9位组织机构代码；
+// 20位：旧版个人税号，18位身份证号码 + 2位顺序码
+pub(crate) fn check_tax_code(tax_code: &str) -> String {
+    let code: String = tax_code
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase();
+    if code.is_empty() {
+        return "".to_string();
+    }
+    if !code.is_ascii() {
+        return TAX_CODE_BAD_FORMAT.to_string();
+    }
+
+    let result = match code.len() {
+        18 => check_credit_code(&code),
+        15 if code[..6].chars().all(|c| c.is_ascii_digit()) => check_organization_code(&code[6..]),
+        20 if code[18..].chars().all(|c| c.is_ascii_digit()) => check_id_card(&code[..18]),
+        _ => TAX_CODE_BAD_FORMAT,
+    };
+    result.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credit_code() {
+        assert_eq!(check_tax_code("91350100M000100Y43"), TAX_CODE_VALID);
+        assert_eq!(check_tax_code("91350100M000100Y44"), TAX_CODE_BAD_CHECKSUM);
+        // 不在字符集中的字母
+        assert_eq!(check_tax_code("91350100M000100Y4I"), TAX_CODE_BAD_FORMAT);
+        // 第3-8位行政区划码必须为数字
+        assert_eq!(check_tax_code("91A50100M000100Y43"), TAX_CODE_BAD_FORMAT);
+    }
+
+    #[test]
+    fn normalizes_case_and_whitespace() {
+        assert_eq!(check_tax_code("91350100m000100y43"), TAX_CODE_VALID);
+        assert_eq!(check_tax_code(" 9135 0100M000100Y43\n"), TAX_CODE_VALID);
+        assert_eq!(check_tax_code("   "), "");
+        assert_eq!(check_tax_code(""), "");
+    }
+
+    #[test]
+    fn legacy_tax_codes() {
+        // 15位：行政区划码 + 组织机构代码
+        assert_eq!(check_tax_code("110108000000019"), TAX_CODE_VALID);
+        assert_eq!(check_tax_code("110108000000018"), TAX_CODE_BAD_CHECKSUM);
+        assert_eq!(check_tax_code("A10108000000019"), TAX_CODE_BAD_FORMAT);
+        // 20位：身份证号码 + 顺序码
+        assert_eq!(check_tax_code("11010519491231002X01"), TAX_CODE_VALID);
+        assert_eq!(check_tax_code("11010519491231002x01"), TAX_CODE_VALID);
+        assert_eq!(
+            check_tax_code("11010519491231002101"),
+            TAX_CODE_BAD_CHECKSUM
+        );
+        assert_eq!(check_tax_code("11010519491231002X0A"), TAX_CODE_BAD_FORMAT);
+    }
+
+    #[test]
+    fn other_lengths_and_characters() {
+        assert_eq!(check_tax_code("12345"), TAX_CODE_BAD_FORMAT);
+        assert_eq!(check_tax_code("9135010"), TAX_CODE_BAD_FORMAT);
+        assert_eq!(
+            check_tax_code("９１３５０１００Ｍ０００１００Ｙ４３"),
+            TAX_CODE_BAD_FORMAT
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    capture_first, join_rows, transport_deductible_tax_cents, Invoice, InvoiceItem, Money,
+    TextItem, TRANSPORT_TAX_RATE_PERCENT,
+};
+
+// 铁路电子客票的发票类型
+pub(crate) const TRAIN_TICKET_TYPE: &str = "电子发票（铁路电子客票）";
+
+// 铁路电子客票信息
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct TrainTicket {
+    passenger_name: String,
+    // 脱敏后的证件号码
+    passenger_id: String,
+    departure_station: String,
+    arrival_station: String,
+    train_number: String,
+    seat_class: String,
+    seat_number: String,
+    travel_date: String,
+    fare: Money,
+    ticket_number: String,
+}
+
+impl TrainTicket {
+    // 导出字段对应的值
+    pub(crate) fn export_value(&self, field: &str) -> Option<&str> {
+        let value = match field {
+            "乘车人" => &self.passenger_name,
+            "证件号" => &self.passenger_id,
+            "出发站" => &self.departure_station,
+            "到达站" => &self.arrival_station,
+            "车次" => &self.train_number,
+            "席别" => &self.seat_class,
+            "乘车日期" => &self.travel_date,
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    // 导出金额字段对应的值，票价与行程单共用"票价"列
+    pub(crate) fn export_amount(&self, field: &str) -> Option<Money> {
+        match field {
+            "票价" => Some(self.fare),
+            _ => None,
+        }
+    }
+
+    // 需要标注来源区域的字段及其值
+    pub(crate) fn source_values(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("passenger_name", self.passenger_name.clone()),
+            ("passenger_id", self.passenger_id.clone()),
+            ("departure_station", self.departure_station.clone()),
+            ("arrival_station", self.arrival_station.clone()),
+            ("train_number", self.train_number.clone()),
+            ("seat_class", self.seat_class.clone()),
+            ("seat_number", self.seat_number.clone()),
+            ("fare", self.fare.to_string()),
+            ("ticket_number", self.ticket_number.clone()),
+        ]
+    }
+}
+
+// 判断是否为铁路电子客票
+pub(crate) fn is_train_ticket(text_items: &[TextItem]) -> bool {
+    text_items
+        .iter()
+        .any(|item| item.text.contains("铁路电子客票") || item.text.starts_with("电子客票号"))
+}
+
+// 提取车站与车次，二者位于同一行：出发站 车次 到达站
+fn extract_stations(rows: &[String], ticket: &mut TrainTicket) {
+    let station_regex = regex::Regex::new(r"([\p{Han}]+)\s*站").unwrap();
+    let train_regex = regex::Regex::new(r"(^|\s)([GDCZTKLYS]\d{1,4}|\d{4})(\s|$)").unwrap();
+
+    for row in rows {
+        if row.contains(':') || row.contains('：') {
+            continue;
+        }
+
+        let stations: Vec<regex::Captures> = station_regex.captures_iter(row).collect();
+        if stations.len() < 2 {
+            continue;
+        }
+
+        let departure = &stations[0];
+        let arrival = &stations[stations.len() - 1];
+        ticket.departure_station = format!("{}站", &departure[1]);
+        ticket.arrival_station = format!("{}站", &arrival[1]);
+
+        // 车次位于两站之间
+        let between = &row[departure.get(0).unwrap().end()..arrival.get(0).unwrap().start()];
+        if let Some(caps) = train_regex.captures(between) {
+            ticket.train_number = caps[2].to_string();
+        }
+        return;
+    }
+}
+
+// 解析铁路电子客票
+pub(crate) fn parse_train_ticket_fapiao(
+    text_items: &[TextItem],
+    mut invoice: Invoice,
+    page_index: usize,
+) -> Invoice {
+    // 设置页面索引
+    invoice.index = page_index + 1;
+    invoice.title = if page_index == 0 {
+        TRAIN_TICKET_TYPE.to_string()
+    } else {
+        format!("{} (第{}页)", TRAIN_TICKET_TYPE, page_index + 1)
+    };
+    invoice.invoice_type = TRAIN_TICKET_TYPE.to_string();
+
+    let rows = join_rows(text_items);
+    let mut ticket = TrainTicket::default();
+
+    // 发票号码与开票日期
+    let number_regex = regex::Regex::new(r"发票号码[:：]?\s*(\d{20})").unwrap();
+    invoice.number = capture_first(&rows, &number_regex, 1);
+    let date_regex =
+        regex::Regex::new(r"开票日期[:：]?\s*(\d{4}\s*年\s*\d{1,2}\s*月\s*\d{1,2}\s*日)").unwrap();
+    invoice.date = capture_first(&rows, &date_regex, 1);
+
+    // 车站与车次
+    extract_stations(&rows, &mut ticket);
+
+    // 乘车日期与发车时间，如"2025年01月11日 09:00开"
+    let travel_regex =
+        regex::Regex::new(r"(\d{4}\s*年\s*\d{1,2}\s*月\s*\d{1,2}\s*日)\s*(\d{1,2}:\d{2})\s*开")
+            .unwrap();
+    if let Some(caps) = rows.iter().find_map(|row| travel_regex.captures(row)) {
+        ticket.travel_date = format!("{} {}", caps[1].replace(' ', ""), &caps[2]);
+    }
+
+    // 车厢座位号与席别
+    let seat_regex = regex::Regex::new(r"(\d{1,2}\s*车\s*\d{1,3}[A-Z]?\s*号)").unwrap();
+    ticket.seat_number = capture_first(&rows, &seat_regex, 1).replace(' ', "");
+    let seat_class_regex = regex::Regex::new(
+        r"(商务座|特等座|一等座|二等座|高级软卧|软卧|硬卧|动卧|一等卧|二等卧|软座|硬座|无座)",
+    )
+    .unwrap();
+    ticket.seat_class = capture_first(&rows, &seat_class_regex, 1);
+
+    // 票价
+    let fare_regex = regex::Regex::new(r"[¥￥]\s*(\d+(\.\d+)?)").unwrap();
+    ticket.fare = Money::parse(&capture_first(&rows, &fare_regex, 1));
+
+    // 乘车人证件号（已脱敏）与姓名
+    let passenger_regex =
+        regex::Regex::new(r"(\d{6,10}\*{4,8}\d{0,4}[\dXx]?)\s*([\p{Han}·]{2,})").unwrap();
+    if let Some(caps) = rows.iter().find_map(|row| passenger_regex.captures(row)) {
+        ticket.passenger_id = caps[1].to_string();
+        ticket.passenger_name = caps[2].to_string();
+    }
+
+    // 电子客票号
+    let ticket_number_regex = regex::Regex::new(r"电子客票号[:：]?\s*(\w+)").unwrap();
+    ticket.ticket_number = capture_first(&rows, &ticket_number_regex, 1);
+
+    // 购买方信息
+    let buyer_name_regex = regex::Regex::new(r"购买方名称[:：]?\s*(\S+)").unwrap();
+    invoice.buyer.name = capture_first(&rows, &buyer_name_regex, 1);
+    let buyer_tax_code_regex =
+        regex::Regex::new(r"统一社会信用代码[:：]?\s*([0-9A-Za-z]{15,20})").unwrap();
+    invoice.buyer.tax_code = capture_first(&rows, &buyer_tax_code_regex, 1);
+
+    // 金额：票价为价税合计，按9%扣除率计算可抵扣税额
+    if let Some(fare_cents) = ticket.fare.cents() {
+        let tax_cents = transport_deductible_tax_cents(fare_cents);
+        invoice.total_amount_tax = Money::from_cents(fare_cents);
+        invoice.total_tax = Money::from_cents(tax_cents);
+        invoice.total_amount = Money::from_cents(fare_cents - tax_cents);
+        invoice.deductible_tax = invoice.total_tax;
+        invoice.deductible = true;
+    }
+
+    invoice.items.push(InvoiceItem {
+        name: format!(
+            "*运输服务*铁路旅客运输 {} {}-{}",
+            ticket.train_number, ticket.departure_station, ticket.arrival_station
+        )
+        .trim()
+        .to_string(),
+        spec: "".to_string(),
+        unit: "".to_string(),
+        quantity: "1".to_string(),
+        price: invoice.total_amount.to_string(),
+        amount: invoice.total_amount,
+        tax_rate: format!("{}%", TRANSPORT_TAX_RATE_PERCENT),
+        tax: invoice.total_tax,
+    });
+
+    invoice.train_ticket = Some(ticket);
+    invoice
+}
@@ -0,0 +1,272 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{tax_id, Invoice, Money};
+
+// 校验未通过时的发票状态
+pub(crate) const REVIEW_STATUS: &str = "需复核";
+
+// 逐行核对税额时允许的舍入误差（分）
+const LINE_TAX_TOLERANCE_CENTS: i64 = 1;
+
+// 校验问题的严重程度：error 需要人工复核，warning 仅作提示
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+// 单条校验结果，fields 为涉及的字段，如 "total_amount"、"items[0].tax"
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ValidationFinding {
+    severity: Severity,
+    fields: Vec<String>,
+    message: String,
+}
+
+impl ValidationFinding {
+    fn new(severity: Severity, fields: &[&str], message: String) -> Self {
+        Self {
+            severity,
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+            message,
+        }
+    }
+
+    pub(crate) fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub(crate) fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub(crate) fn fields(&self) -> &[String] {
+        &self.fields
+    }
+}
+
+// 是否存在需要人工复核的问题
+pub(crate) fn needs_review(findings: &[ValidationFinding]) -> bool {
+    findings
+        .iter()
+        .any(|finding| finding.severity == Severity::Error)
+}
+
+// 解析税率，如"13%" -> 13.0，免税/不征税视为0，无法识别时返回 None
+fn parse_tax_rate(tax_rate: &str) -> Option<f64> {
+    let tax_rate = tax_rate.trim();
+    if tax_rate.contains("免税") || tax_rate.contains("不征税") {
+        return Some(0.0);
+    }
+    tax_rate.strip_suffix('%')?.trim().parse::<f64>().ok()
+}
+
+// 累加明细金额，任一明细缺失时返回 None
+fn sum_items(values: impl Iterator<Item = Money>) -> Option<i64> {
+    values.map(|value| value.cents()).sum::<Option<i64>>()
+}
+
+// 核对发票金额：合计金额 + 合计税额 = 价税合计，明细合计与发票合计一致，每行金额 × 税率 ≈ 税额
+pub(crate) fn validate_invoice(invoice: &Invoice) -> Vec<ValidationFinding> {
+    let mut findings = Vec::new();
+
+    // 必要金额缺失
+    if invoice.total_amount.is_missing() {
+        findings.push(ValidationFinding::new(
+            Severity::Error,
+            &["total_amount"],
+            "未识别合计金额".to_string(),
+        ));
+    }
+    if invoice.total_tax.is_missing() {
+        findings.push(ValidationFinding::new(
+            Severity::Warning,
+            &["total_tax"],
+            "未识别合计税额".to_string(),
+        ));
+    }
+    if invoice.total_amount_tax.is_missing() {
+        findings.push(ValidationFinding::new(
+            Severity::Error,
+            &["total_amount_tax"],
+            "未识别价税合计".to_string(),
+        ));
+    }
+
+    // 合计金额 + 合计税额 = 价税合计
+    if let (Some(amount), Some(tax), Some(total)) = (
+        invoice.total_amount.cents(),
+        invoice.total_tax.cents(),
+        invoice.total_amount_tax.cents(),
+    ) {
+        if amount + tax != total {
+            findings.push(ValidationFinding::new(
+                Severity::Error,
+                &["total_amount", "total_tax", "total_amount_tax"],
+                format!(
+                    "合计金额 {} + 合计税额 {} 不等于价税合计 {}",
+                    invoice.total_amount, invoice.total_tax, invoice.total_amount_tax
+                ),
+            ));
+        }
+    }
+
+    // 价税合计大小写一致
+    if let (Some(total), Some(upper)) = (
+        invoice.total_amount_tax.cents(),
+        invoice.total_amount_tax_upper_value.cents(),
+    ) {
+        if total != upper {
+            findings.push(ValidationFinding::new(
+                Severity::Error,
+                &["total_amount_tax", "total_amount_tax_upper"],
+                format!(
+                    "价税合计小写 {} 与大写\"{}\"（{}）不一致",
+                    invoice.total_amount_tax,
+                    invoice.total_amount_tax_upper,
+                    invoice.total_amount_tax_upper_value
+                ),
+            ));
+        }
+    } else if !invoice.total_amount_tax_upper.is_empty()
+        && invoice.total_amount_tax_upper_value.is_missing()
+    {
+        findings.push(ValidationFinding::new(
+            Severity::Warning,
+            &["total_amount_tax_upper"],
+            format!("价税合计大写\"{}\"无法识别", invoice.total_amount_tax_upper),
+        ));
+    }
+
+    // 开票日期无法识别时，排序和按日期筛选会遗漏该发票
+    if invoice.date_value.is_none() {
+        let message = if invoice.date.trim().is_empty() {
+            "未识别开票日期".to_string()
+        } else {
+            format!("开票日期\"{}\"无法识别", invoice.date)
+        };
+        findings.push(ValidationFinding::new(
+            Severity::Warning,
+            &["date"],
+            message,
+        ));
+    }
+
+    // 纳税人识别号校验
+    for (party, field, status) in [
+        ("购买方", "buyer.tax_code", &invoice.buyer.tax_code_status),
+        ("销售方", "seller.tax_code", &invoice.seller.tax_code_status),
+    ] {
+        if status == tax_id::TAX_CODE_BAD_CHECKSUM || status == tax_id::TAX_CODE_BAD_FORMAT {
+            findings.push(ValidationFinding::new(
+                Severity::Error,
+                &[field],
+                format!("{}纳税人识别号{}", party, status),
+            ));
+        }
+    }
+
+    // 未识别出任何明细金额时不核对明细
+    if invoice
+        .items
+        .iter()
+        .all(|item| item.amount.is_missing() && item.tax.is_missing())
+    {
+        return findings;
+    }
+
+    // 明细金额合计 = 合计金额
+    match sum_items(invoice.items.iter().map(|item| item.amount)) {
+        Some(items_amount) => {
+            if let Some(amount) = invoice.total_amount.cents() {
+                if items_amount != amount {
+                    findings.push(ValidationFinding::new(
+                        Severity::Error,
+                        &["items.amount", "total_amount"],
+                        format!(
+                            "明细金额合计 {} 与合计金额 {} 不一致",
+                            Money::from_cents(items_amount),
+                            invoice.total_amount
+                        ),
+                    ));
+                }
+            }
+        }
+        None => findings.push(ValidationFinding::new(
+            Severity::Warning,
+            &["items.amount"],
+            "部分明细未识别金额".to_string(),
+        )),
+    }
+
+    // 明细税额合计 = 合计税额
+    match sum_items(invoice.items.iter().map(|item| item.tax)) {
+        Some(items_tax) => {
+            if let Some(tax) = invoice.total_tax.cents() {
+                if items_tax != tax {
+                    findings.push(ValidationFinding::new(
+                        Severity::Error,
+                        &["items.tax", "total_tax"],
+                        format!(
+                            "明细税额合计 {} 与合计税额 {} 不一致",
+                            Money::from_cents(items_tax),
+                            invoice.total_tax
+                        ),
+                    ));
+                }
+            }
+        }
+        None => findings.push(ValidationFinding::new(
+            Severity::Warning,
+            &["items.tax"],
+            "部分明细未识别税额".to_string(),
+        )),
+    }
+
+    // 客票的税额按扣除率倒算，且行程单的民航发展基金不计税，不逐行核对
+    if invoice.train_ticket.is_some() || invoice.air_itinerary.is_some() {
+        return findings;
+    }
+
+    // 每行金额 × 税率 ≈ 税额
+    for (i, item) in invoice.items.iter().enumerate() {
+        let (amount, tax) = match (item.amount.cents(), item.tax.cents()) {
+            (Some(amount), Some(tax)) => (amount, tax),
+            _ => continue,
+        };
+        let amount_field = format!("items[{}].amount", i);
+        let rate_field = format!("items[{}].tax_rate", i);
+        let tax_field = format!("items[{}].tax", i);
+
+        let rate = match parse_tax_rate(&item.tax_rate) {
+            Some(rate) => rate,
+            None => {
+                findings.push(ValidationFinding::new(
+                    Severity::Warning,
+                    &[&rate_field],
+                    format!("第{}行明细税率\"{}\"无法识别", i + 1, item.tax_rate),
+                ));
+                continue;
+            }
+        };
+
+        let expected_tax = (amount as f64 * rate / 100.0).round() as i64;
+        if (expected_tax - tax).abs() > LINE_TAX_TOLERANCE_CENTS {
+            findings.push(ValidationFinding::new(
+                Severity::Error,
+                &[&amount_field, &rate_field, &tax_field],
+                format!(
+                    "第{}行明细金额 {} × 税率 {} 应为 {}，实际税额 {}",
+                    i + 1,
+                    item.amount,
+                    item.tax_rate,
+                    Money::from_cents(expected_tax),
+                    item.tax
+                ),
+            ));
+        }
+    }
+
+    findings
+}
@@ -48,7 +48,7 @@ const ConfigPanel: React.FC<ConfigPanelProps> = ({
   setSelectedInvoice,
 }) => {
   // 发票状态类型
-  type InvoiceStatus = "待统计" | "正常" | "重复" | "解析失败";
+  type InvoiceStatus = "待统计" | "正常" | "需复核" | "抬头不符" | "疑似重复" | "重复" | "解析失败";
 
   // 发票基本信息类型
   interface InvoiceBasic {
@@ -108,20 +108,22 @@ const ConfigPanel: React.FC<ConfigPanelProps> = ({
   
   // 导出字段选择状态
   const [exportFields, setExportFields] = useState<string[]>([
-    "序号", "文件名", "状态", "发票代码", "发票号码", "开票日期", 
-    "购买方名称", "购买方税号", "购买方地址、电话", "购买方开户行账号", 
-    "销售方名称", "销售方税号", "销售方地址电话", "销售方开户行账号", 
+    "序号", "文件名", "状态", "发票类型", "发票代码", "发票号码", "开票日期", 
+    "购买方名称", "购买方税号", "购买方税号校验", "购买方地址、电话", "购买方开户行账号", 
+    "销售方名称", "销售方税号", "销售方税号校验", "销售方地址电话", "销售方开户行账号", 
     "收款人", "复核人", "开票人",
-    "金额", "税额", "价税合计", "备注", "重复信息"
+    "金额", "税额", "价税合计", "价税合计(大写)", "可抵扣", "可抵扣税额", "备注", "重复信息", "校验信息", "抬头校验", "人工修正", "置信度", "红冲信息"
   ]);
   
   // 可用的导出字段列表
   const availableExportFields = [
-    "序号", "文件名", "状态", "发票代码", "发票号码", "开票日期", 
-    "购买方名称", "购买方税号", "购买方地址、电话", "购买方开户行账号", 
-    "销售方名称", "销售方税号", "销售方地址电话", "销售方开户行账号", 
+    "序号", "文件名", "状态", "发票类型", "发票代码", "发票号码", "开票日期", 
+    "购买方名称", "购买方税号", "购买方税号校验", "购买方地址、电话", "购买方开户行账号", 
+    "销售方名称", "销售方税号", "销售方税号校验", "销售方地址电话", "销售方开户行账号", 
     "收款人", "复核人", "开票人",
-    "金额", "税额", "价税合计", "备注", "重复信息"
+    "金额", "税额", "价税合计", "价税合计(大写)", "可抵扣", "可抵扣税额", "备注", "重复信息", "校验信息", "抬头校验", "人工修正", "置信度", "红冲信息",
+    "乘车人", "证件号", "出发站", "到达站", "车次", "席别", "乘车日期",
+    "旅客姓名", "电子客票号码", "航段", "票价", "民航发展基金", "燃油附加费"
   ];
   
   // 添加展开行的状态控制
@@ -373,27 +375,8 @@ const ConfigPanel: React.FC<ConfigPanelProps> = ({
         throw new Error("未选择任何文件");
       }
 
-      // 读取文件内容
-      const filePromises = filePaths.map(async (path: string) => {
-        // 使用Tauri API读取文件
-        const fileBytes = await invoke<number[]>("read_file_to_bytes", {
-          path,
-        });
-        const fileArrayBuffer = new Uint8Array(fileBytes).buffer;
-
-        // 从路径中提取文件名
-        const filename = path.substring(path.lastIndexOf("\\") + 1);
-
-        // 创建File对象
-        return new File([fileArrayBuffer], filename, {
-          type: "application/pdf",
-        });
-      });
-
-      const files = await Promise.all(filePromises);
-
-      // 使用前端PDF解析服务处理文件
-      await pdfService.parsePdfFiles(files);
+      // 由后端并行解析全部文件，通过事件报告进度
+      await pdfService.parsePdfPaths(filePaths);
 
       // 处理完成，更新一次最终状态
       const finalStats = pdfService.getStats();
@@ -473,7 +456,7 @@ const ConfigPanel: React.FC<ConfigPanelProps> = ({
       const field = sorterState.field || "";
       
       if (field === "status") {
-        const statusOrder = {"正常": 1, "重复": 2, "解析失败": 3, "待统计": 4};
+        const statusOrder = {"正常": 1, "需复核": 2, "抬头不符": 3, "疑似重复": 4, "重复": 5, "解析失败": 6, "待统计": 7};
         return (a: InvoiceBasic, b: InvoiceBasic) => 
           statusOrder[a.status] - statusOrder[b.status];
       }
@@ -725,12 +708,15 @@ const ConfigPanel: React.FC<ConfigPanelProps> = ({
       align: "center",
       showSorterTooltip: false,
       sorter: (a, b) => {
-        const statusOrder = {"正常": 1, "重复": 2, "解析失败": 3, "待统计": 4};
+        const statusOrder = {"正常": 1, "需复核": 2, "抬头不符": 3, "疑似重复": 4, "重复": 5, "解析失败": 6, "待统计": 7};
         return statusOrder[a.status] - statusOrder[b.status];
       },
       render: (status: string) => {
         let color = "default";
         if (status === "正常") color = "success";
+        else if (status === "需复核") color = "orange";
+        else if (status === "抬头不符") color = "magenta";
+        else if (status === "疑似重复") color = "volcano";
         else if (status === "重复") color = "error";
         else if (status === "解析失败") color = "warning";
 
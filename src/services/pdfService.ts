@@ -1,5 +1,6 @@
 import { invoke } from "@tauri-apps/api/core";
-import { parsePdfFile, Invoice, ProgressCallback } from "../utils/pdfParser";
+import { listen } from "@tauri-apps/api/event";
+import { parsePdfFile, Invoice, Correction, SourceBox, ProgressCallback } from "../utils/pdfParser";
 
 // 处理状态接口
 export interface ProcessingStats {
@@ -12,6 +13,184 @@ export interface ProcessingStats {
   currentProgress: number; // 添加当前进度字段
 }
 
+// 后端批量解析或导出任务
+export interface JobInfo {
+  id: number;
+  kind: "parse" | "export";
+  status: "running" | "completed" | "cancelled" | "failed";
+  total: number; // 解析任务为文件数，导出任务为发票数
+  done: number;
+  message: string; // 失败原因
+}
+
+// 后端批量解析进度事件
+export interface BatchProgress {
+  job_id: number;
+  kind: "started" | "page_done" | "finished" | "failed";
+  path: string;
+  filename: string;
+  page: number; // 当前文件已解析页数
+  page_count: number; // 当前文件总页数
+  invoice_count: number; // 当前文件解析出的发票数，仅在解析完成时有效
+  error: string;
+  total_files: number;
+  finished_files: number; // 已完成（含失败）的文件数
+  failed_files: number;
+}
+
+// 一组重复发票，indexes 中第一个为最先出现的发票序号
+export interface DuplicateGroup {
+  group: number;
+  reason: string;
+  indexes: number[];
+}
+
+// 分组合计，按税率分组时 count 为明细行数，按发票类型分组时为发票数
+export interface StatsBreakdown {
+  key: string;
+  count: number;
+  amount: string;
+  tax: string;
+  amount_tax: string;
+}
+
+// 后端统计信息，金额为字符串
+export interface BackendStats {
+  total_amount: string;
+  total_tax: string;
+  total_amount_tax: string;
+  invoice_count: number;
+  duplicate_count: number;
+  suspected_duplicate_count: number;
+  success_count: number;
+  fail_count: number;
+  review_count: number;
+  buyer_mismatch_count: number;
+  by_tax_rate: StatsBreakdown[]; // 正常发票按税率分组
+  by_invoice_type: StatsBreakdown[]; // 正常发票按发票类型分组
+  red_letter_count: number; // 正常发票中的红字发票数，金额已与正数发票冲抵
+}
+
+// 后端重复检查结果
+interface DuplicateDetection {
+  invoices: Invoice[];
+  groups: DuplicateGroup[];
+  stats: BackendStats;
+}
+
+// 人工修正日志中的一条，附带所属发票信息
+export interface CorrectionEntry extends Correction {
+  index: number;
+  filename: string;
+  number: string;
+}
+
+// 发票列表筛选条件，未填写的条件不参与筛选
+export interface InvoiceFilter {
+  status?: string[];
+  date_from?: string; // 开票日期范围，如 2024-05-01，包含起止日期
+  date_to?: string;
+  seller?: string; // 销售方名称或税号
+  amount_min?: string; // 价税合计范围
+  amount_max?: string;
+  invoice_type?: string;
+}
+
+// 发票列表查询
+export interface InvoiceQuery {
+  filter?: InvoiceFilter;
+  sort_by?: "index" | "date" | "amount" | "seller" | "status" | "invoice_type" | "number" | "confidence";
+  descending?: boolean;
+  page?: number; // 页码从1开始
+  page_size?: number; // 默认50
+}
+
+// 一页发票列表，total 为符合筛选条件的发票总数
+export interface InvoicePage {
+  invoices: Invoice[];
+  total: number;
+  page: number;
+  page_size: number;
+}
+
+// 发票台账记录
+export interface LedgerEntry {
+  id: number;
+  code: string;
+  number: string;
+  date: string;
+  seller_name: string;
+  buyer_name: string;
+  total_amount_tax: string;
+  filename: string;
+  file_hash: string;
+  page: number;
+  batch: string; // 导入批次
+  imported_at: string;
+  reimbursed: boolean;
+  reimbursed_date: string;
+}
+
+// 发票台账查询条件
+export interface LedgerQuery {
+  keyword?: string; // 匹配发票代码、号码、销售方、购买方和文件名
+  batch?: string;
+  reimbursed?: boolean;
+  limit?: number;
+}
+
+// 本公司抬头
+export interface BuyerEntity {
+  name: string;
+  tax_code: string;
+}
+
+// 相对锚点的搜索区域
+export interface LayoutRegion {
+  anchor: string;
+  left: number;
+  right: number | null;
+  top: number;
+  bottom: number;
+}
+
+// 发票版式模板，path 为用户模板的文件路径，内置模板为空
+export interface LayoutTemplate {
+  name: string;
+  invoice_types: string[];
+  detect: string;
+  party: {
+    buyer_anchor: string;
+    seller_anchor: string;
+    left: number;
+    right: number;
+    vertical: number;
+    stop_at_password: boolean;
+    labels: Record<"name" | "tax_code" | "address_phone" | "bank_account", string>;
+    row_tolerance: number;
+  } | null;
+  party_columns: {
+    buyer_anchor: string;
+    seller_anchor: string;
+    left: number;
+    top: number;
+    bottom: number;
+    fallback_height: number;
+    labels: Record<"name" | "tax_code" | "address_phone" | "bank_account", string>;
+    row_tolerance: number;
+    label_distance: number;
+  } | null;
+  remark: LayoutRegion | null;
+  fields: (LayoutRegion & { field: string; pattern: string })[];
+  path: string;
+}
+
+// 重新读取版式模板的结果，errors 为被跳过的模板文件的错误
+export interface LayoutReload {
+  templates: LayoutTemplate[];
+  errors: string[];
+}
+
 /**
  * 发票处理服务
  */
@@ -34,6 +213,9 @@ class PdfService {
   private totalFiles = 0;
   // 当前处理的文件索引
   private currentFileIndex = 0;
+  // 正在运行的后端解析任务和导出任务ID
+  private parseJobId: number | null = null;
+  private exportJobId: number | null = null;
 
   /**
    * 解析PDF文件
@@ -57,13 +239,11 @@ class PdfService {
     this.stats.currentProgress = 1;
 
     try {
+      // 开始新的导入批次，本批次发票将与台账中之前批次的发票比较
+      await invoke<string>("start_import_batch");
+
       // 处理每个文件
       let index = 1;
-      let successCount = 0;
-      let duplicateCount = 0;
-      let failCount = 0;
-      let totalAmount = 0;
-      let totalAmountTax = 0;
 
       for (const file of files) {
         // 检查是否应该停止处理
@@ -106,74 +286,19 @@ class PdfService {
           const invoices = await parsePdfFile(file, progressCallback);
           // console.log(`文件解析完成: ${file.name}, 共 ${invoices.length} 页发票`);
 
-          // 处理每个发票
-          for (const invoice of invoices) {
-            // 检查是否应该停止处理
-            if (!this.isProcessing) {
-              console.log("处理已停止，不再继续处理剩余发票");
-              break;
-            }
-            
-            // 设置序号
-            invoice.index = index;
-
-            // 检查是否重复（根据发票号码和发票代码来检查）
-            let isDuplicate = false;
-            let duplicateWithIndex = 0;
-
-            // 只有当发票代码和号码不是空或未解析时才检查重复性
-            if (
-              invoice.code &&
-              invoice.code !== "未能解析" &&
-              invoice.number &&
-              !invoice.number.startsWith("文件：")
-            ) {
-              for (const existingInvoice of this.invoices) {
-                if (
-                  existingInvoice.number === invoice.number &&
-                  existingInvoice.code === invoice.code
-                ) {
-                  isDuplicate = true;
-                  duplicateWithIndex = existingInvoice.index;
-                  break;
-                }
-              }
-            }
+          // 检查是否应该停止处理
+          if (!this.isProcessing) {
+            console.log("处理已停止，不再继续处理剩余发票");
+            break;
+          }
 
-            if (invoice.status === "解析失败") {
-              // 如果是解析失败状态，保持该状态
-              failCount++;
-            } else if (isDuplicate) {
-              duplicateCount++;
-              invoice.status = "重复";
-              invoice.duplicate_info = `与第${duplicateWithIndex}个发票重复`;
-            } else {
-              successCount++;
-              invoice.status = "正常";
-              
-              // 解析金额和税额，用于统计
-              const amount = parseFloat(invoice.total_amount) || 0;
-              const tax = parseFloat(invoice.total_amount_tax) || 0;
-              totalAmount += amount;
-              totalAmountTax += tax;
-            }
+          this.invoices.push(...invoices);
+          index += invoices.length;
 
-            this.invoices.push(invoice);
-            index++;
-            
-            // 每添加一个发票后立即更新统计信息，便于跟踪进度
-            this.updateStats(
-              successCount,
-              duplicateCount,
-              failCount,
-              totalAmount,
-              totalAmountTax,
-              this.invoices.length
-            );
-          }
+          // 每个文件解析后由后端重新检查重复并更新统计信息，便于跟踪进度
+          await this.detectDuplicates();
         } catch (error) {
           console.error("解析文件失败:", error);
-          failCount++;
 
           // 创建解析失败的发票记录
           const failedInvoice: Invoice = {
@@ -203,6 +328,8 @@ class PdfService {
             items: [
               {
                 name: "无法识别的商品",
+                spec: "",
+                unit: "",
                 quantity: "0",
                 price: "0",
                 amount: "0",
@@ -218,23 +345,20 @@ class PdfService {
             drawer: "",
             status: "解析失败",
             duplicate_info: "",
+            deductible: false,
           };
 
           this.invoices.push(failedInvoice);
           index++;
           
           // 解析失败后也要更新统计信息
-          this.updateStats(
-            successCount,
-            duplicateCount,
-            failCount,
-            totalAmount,
-            totalAmountTax,
-            this.invoices.length
-          );
+          await this.detectDuplicates();
         }
       }
       
+      // 将本批次正常的发票记入台账，之后再次导入时可识别为重复
+      await invoke<number>("record_to_ledger");
+
       // 全部文件处理完成，设置进度为100%
       this.stats.currentProgress = 100;
       // console.log("所有文件处理完成，进度设为100%");
@@ -249,6 +373,69 @@ class PdfService {
     }
   }
 
+  /**
+   * 由后端并行解析PDF文件，适用于大量文件
+   * @param paths PDF文件路径列表
+   * @returns 处理状态
+   */
+  async parsePdfPaths(paths: string[]): Promise<ProcessingStats> {
+    if (this.isProcessing) {
+      throw new Error("已经有一个解析进程在运行");
+    }
+
+    this.isProcessing = true;
+    this.invoices = [];
+    this.resetStats();
+    this.totalFiles = paths.length;
+    this.currentFileIndex = 0;
+    this.stats.currentProgress = 1;
+
+    // 各文件的解析进度（0-1），用于计算总进度
+    const fileProgress = new Map<string, number>();
+    const unlisten = await listen<BatchProgress>("batch-progress", (event) => {
+      const progress = event.payload;
+      this.parseJobId = progress.job_id;
+      if (progress.kind === "page_done" && progress.page_count > 0) {
+        fileProgress.set(progress.path, progress.page / progress.page_count);
+      } else if (progress.kind === "finished" || progress.kind === "failed") {
+        fileProgress.set(progress.path, 1);
+      }
+      this.currentFileIndex = progress.finished_files;
+
+      let completed = 0;
+      fileProgress.forEach((value) => (completed += value));
+      const totalProgress = Math.floor((completed / progress.total_files) * 99);
+      // 只有当新进度大于当前进度时才更新，避免进度回退
+      if (totalProgress > this.stats.currentProgress) {
+        this.stats.currentProgress = totalProgress;
+      }
+    });
+
+    try {
+      // 开始新的导入批次，本批次发票将与台账中之前批次的发票比较
+      await invoke<string>("start_import_batch");
+
+      // 停止时后端取消任务并返回已解析的发票
+      this.invoices = await invoke<Invoice[]>("parse_invoice_files", { paths });
+      await this.detectDuplicates();
+
+      // 将本批次正常的发票记入台账，之后再次导入时可识别为重复
+      await invoke<number>("record_to_ledger");
+
+      // 全部文件处理完成，设置进度为100%
+      this.stats.currentProgress = 100;
+
+      return this.stats;
+    } catch (error) {
+      console.error("PDF处理过程中发生错误:", error);
+      throw error;
+    } finally {
+      unlisten();
+      this.parseJobId = null;
+      this.isProcessing = false;
+    }
+  }
+
   /**
    * 获取所有发票
    */
@@ -277,6 +464,45 @@ class PdfService {
   stopProcessing(): void {
     // console.log("接收到停止处理请求");
     this.isProcessing = false;
+
+    // 取消后端解析任务，已解析的发票会保留
+    if (this.parseJobId !== null) {
+      this.cancelJob(this.parseJobId).catch((error) =>
+        console.error("取消解析任务失败:", error)
+      );
+    }
+  }
+
+  /**
+   * 取消正在进行的导出
+   */
+  async cancelExport(): Promise<void> {
+    if (this.exportJobId !== null) {
+      await this.cancelJob(this.exportJobId);
+    }
+  }
+
+  /**
+   * 查询后端任务状态
+   * @param jobId 任务ID
+   */
+  async getJob(jobId: number): Promise<JobInfo> {
+    return await invoke<JobInfo>("get_job", { jobId });
+  }
+
+  /**
+   * 列出后端任务
+   */
+  async listJobs(): Promise<JobInfo[]> {
+    return await invoke<JobInfo[]>("list_jobs");
+  }
+
+  /**
+   * 取消后端任务，已结束的任务不受影响
+   * @param jobId 任务ID
+   */
+  async cancelJob(jobId: number): Promise<JobInfo> {
+    return await invoke<JobInfo>("cancel_job", { jobId });
   }
 
   /**
@@ -328,14 +554,16 @@ class PdfService {
           name: "",
           tax_code: "",
           address_phone: "",
-          bank_account: ""
+          bank_account: "",
+          tax_code_status: ""
         };
         
         let seller = {
           name: "",
           tax_code: "",
           address_phone: "",
-          bank_account: ""
+          bank_account: "",
+          tax_code_status: ""
         };
         
         // 处理buyer信息
@@ -344,7 +572,8 @@ class PdfService {
             name: invoice.buyer.name || "",
             tax_code: invoice.buyer.taxCode || invoice.buyer.tax_code || "",
             address_phone: invoice.buyer.addressPhone || invoice.buyer.address_phone || "",
-            bank_account: invoice.buyer.bankAccount || invoice.buyer.bank_account || ""
+            bank_account: invoice.buyer.bankAccount || invoice.buyer.bank_account || "",
+            tax_code_status: invoice.buyer.tax_code_status || ""
           };
         }
         
@@ -354,12 +583,17 @@ class PdfService {
             name: invoice.seller.name || "",
             tax_code: invoice.seller.taxCode || invoice.seller.tax_code || "",
             address_phone: invoice.seller.addressPhone || invoice.seller.address_phone || "",
-            bank_account: invoice.seller.bankAccount || invoice.seller.bank_account || ""
+            bank_account: invoice.seller.bankAccount || invoice.seller.bank_account || "",
+            tax_code_status: invoice.seller.tax_code_status || ""
           };
         }
         
+        // 保留后端解析出的其他字段（如可抵扣标记）
+        const original = this.invoices.find(inv => inv.filename === invoice.filename);
+
         // 创建一个新的完整发票对象，映射所有字段
         const processedInvoice: any = {
+          ...original,
           // 基本字段
           filename: invoice.filename,
           index: invoice.index,
@@ -374,10 +608,10 @@ class PdfService {
           password: invoice.password || "",
           remark: invoice.remark || "",
           
-          // 金额相关
-          total_amount: invoice.totalAmount || invoice.total_amount || "0.00",
-          total_tax: invoice.totalTax || invoice.total_tax || "0.00",
-          total_amount_tax: invoice.totalAmountTax || invoice.total_amount_tax || "0.00",
+          // 金额相关，空字符串表示金额缺失，不能按0处理
+          total_amount: invoice.totalAmount ?? invoice.total_amount ?? "",
+          total_tax: invoice.totalTax ?? invoice.total_tax ?? "",
+          total_amount_tax: invoice.totalAmountTax ?? invoice.total_amount_tax ?? "",
           
           // 人员信息
           payee: invoice.payee || "",
@@ -398,6 +632,8 @@ class PdfService {
           const items = invoice.details || invoice.items || [];
           processedInvoice.items = items.map((item: any) => ({
             name: item.name || "",
+            spec: item.spec || "",
+            unit: item.unit || "",
             quantity: item.quantity || "0",
             price: item.price || "0",
             amount: item.amount || "0",
@@ -414,13 +650,26 @@ class PdfService {
       // 先将处理好的发票数据发送到后端
       await invoke("set_invoices", { invoices: processedInvoices });
 
-      // 然后导出Excel
-      await invoke("export_results", { 
-        path: options.path,
-        filename: options.filename || "发票数据汇总",
-        exportWithDetails: options.exportWithDetails,
-        exportFields: options.exportFields
+      // 记录导出任务ID，以便取消导出
+      const unlisten = await listen<JobInfo>("job-update", (event) => {
+        if (event.payload.kind === "export") {
+          this.exportJobId =
+            event.payload.status === "running" ? event.payload.id : null;
+        }
       });
+
+      // 然后导出Excel
+      try {
+        await invoke("export_results", {
+          path: options.path,
+          filename: options.filename || "发票数据汇总",
+          exportWithDetails: options.exportWithDetails,
+          exportFields: options.exportFields
+        });
+      } finally {
+        unlisten();
+        this.exportJobId = null;
+      }
     } catch (error) {
       console.error("导出失败:", error);
       throw error;
@@ -443,27 +692,139 @@ class PdfService {
   }
 
   /**
-   * 更新统计信息
+   * 由后端检查重复发票（发票号码相同或文件内容相同），并更新序号、状态和统计信息
+   */
+  private async detectDuplicates(): Promise<void> {
+    const result = await invoke<DuplicateDetection>("detect_duplicates", {
+      invoices: this.invoices,
+    });
+    this.applyDetection(result);
+  }
+
+  /**
+   * 使用后端返回的发票数据和统计信息
    */
-  private updateStats(
-    successCount: number,
-    duplicateCount: number,
-    failCount: number,
-    totalAmount: number,
-    totalAmountTax: number,
-    invoiceCount: number
-  ): void {
+  private applyDetection(result: DuplicateDetection): void {
+    this.invoices = result.invoices;
     this.stats = {
-      totalAmount: totalAmount,
-      totalAmountTax: totalAmountTax,
-      invoiceCount: invoiceCount,
-      duplicateCount: duplicateCount,
-      successCount: successCount,
-      failCount: failCount,
+      totalAmount: parseFloat(result.stats.total_amount) || 0,
+      totalAmountTax: parseFloat(result.stats.total_amount_tax) || 0,
+      invoiceCount: result.stats.invoice_count,
+      duplicateCount: result.stats.duplicate_count,
+      successCount: result.stats.success_count,
+      failCount: result.stats.fail_count,
       currentProgress: this.stats.currentProgress, // 保持当前进度不变
     };
   }
 
+  /**
+   * 人工修正发票或明细行的一个字段，后端记录原值、新值、时间和修正人
+   * @param index 发票序号
+   * @param field 字段名，如 number、seller.tax_code、total_amount
+   * @param value 修正后的值
+   * @param item 明细行下标（从0开始），修正发票字段时不传
+   * @param user 修正人，不传时使用系统用户名
+   * @param source 在PDF预览中重新框选的来源区域
+   */
+  async correctInvoiceField(
+    index: number,
+    field: string,
+    value: string,
+    item: number | null = null,
+    user: string | null = null,
+    source: SourceBox | null = null
+  ): Promise<Invoice[]> {
+    const result = await invoke<DuplicateDetection>("correct_invoice_field", {
+      index,
+      item,
+      field,
+      value,
+      user,
+      source,
+    });
+    this.applyDetection(result);
+    return this.invoices;
+  }
+
+  /**
+   * 获取人工修正日志
+   */
+  async listCorrections(): Promise<CorrectionEntry[]> {
+    return await invoke<CorrectionEntry[]>("list_corrections");
+  }
+
+  /**
+   * 获取后端统计信息，包括按税率和发票类型的分组合计
+   */
+  async fetchStats(): Promise<BackendStats> {
+    return await invoke<BackendStats>("get_stats");
+  }
+
+  /**
+   * 按条件筛选、排序并分页查询发票
+   * @param query 查询条件
+   */
+  async listInvoices(query: InvoiceQuery = {}): Promise<InvoicePage> {
+    return await invoke<InvoicePage>("list_invoices", { query });
+  }
+
+  /**
+   * 获取本公司抬头白名单
+   */
+  async getBuyerWhitelist(): Promise<BuyerEntity[]> {
+    return await invoke<BuyerEntity[]>("get_buyer_whitelist");
+  }
+
+  /**
+   * 保存本公司抬头白名单，之后解析的发票将按新白名单核对购买方
+   * @param entities 本公司主体列表
+   */
+  async setBuyerWhitelist(entities: BuyerEntity[]): Promise<void> {
+    await invoke("set_buyer_whitelist", { entities });
+  }
+
+  /**
+   * 获取当前使用的发票版式模板，按匹配顺序排列
+   */
+  async getLayoutTemplates(): Promise<LayoutTemplate[]> {
+    return await invoke<LayoutTemplate[]>("get_layout_templates");
+  }
+
+  /**
+   * 重新读取应用配置目录 layouts 下的版式模板，之后解析的发票使用新模板
+   * 有错误的模板文件被跳过，其错误随当前使用的模板一起返回
+   */
+  async reloadLayoutTemplates(): Promise<LayoutReload> {
+    return await invoke<LayoutReload>("reload_layout_templates");
+  }
+
+  /**
+   * 查询发票台账
+   * @param query 查询条件
+   */
+  async searchLedger(query: LedgerQuery = {}): Promise<LedgerEntry[]> {
+    return await invoke<LedgerEntry[]>("search_ledger", { query });
+  }
+
+  /**
+   * 删除台账记录
+   * @param ids 台账记录ID
+   * @returns 删除的记录数
+   */
+  async removeLedgerEntries(ids: number[]): Promise<number> {
+    return await invoke<number>("remove_ledger_entries", { ids });
+  }
+
+  /**
+   * 标记台账记录已报销
+   * @param ids 台账记录ID
+   * @param date 报销日期（YYYY-MM-DD），为 null 时取消报销标记
+   * @returns 更新的记录数
+   */
+  async markReimbursed(ids: number[], date: string | null): Promise<number> {
+    return await invoke<number>("mark_reimbursed", { ids, date });
+  }
+
   /**
    * 获取处理状态
    * @returns 是否正在处理
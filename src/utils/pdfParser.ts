@@ -13,6 +13,8 @@ export type ProgressCallback = (current: number, total: number) => void;
 // 发票项目类型
 export interface InvoiceItem {
   name: string;
+  spec: string; // 规格型号
+  unit: string; // 单位
   quantity: string;
   price: string;
   amount: string;
@@ -26,6 +28,7 @@ export interface InvoiceParty {
   tax_code: string;
   address_phone: string;
   bank_account: string;
+  tax_code_status?: string; // 纳税人识别号校验结果：有效/校验位错误/格式错误
 }
 
 // 发票信息结构
@@ -36,7 +39,8 @@ export interface Invoice {
   invoice_type: string;
   code: string;
   number: string;
-  date: string;
+  date: string; // 票面上的开票日期文本
+  date_value?: string | null; // 解析后的开票日期，如 2024-05-06，无法识别时为 null
   checksum: string;
   machine_number: string;
   password: string;
@@ -47,11 +51,110 @@ export interface Invoice {
   total_amount: string;
   total_tax: string;
   total_amount_tax: string;
+  total_amount_tax_upper?: string; // 价税合计（大写）
+  total_amount_tax_upper_value?: string; // 大写金额对应的数值
   payee: string;
   reviewer: string;
   drawer: string;
   status: string;
   duplicate_info: string;
+  deductible?: boolean; // 进项税额是否可抵扣
+  deductible_tax?: string; // 可抵扣进项税额
+  train_ticket?: TrainTicket | null; // 铁路电子客票信息
+  air_itinerary?: AirItinerary | null; // 航空运输电子客票行程单信息
+  validation?: ValidationFinding[]; // 金额交叉校验结果
+  buyer_mismatch?: string; // 购买方与本公司抬头白名单不符的原因
+  duplicate_group?: number; // 重复组编号，0 表示不重复
+  file_hash?: string; // 文件内容 SHA-256
+  page?: number; // 发票所在页码，从1开始
+  corrections?: Correction[]; // 人工修正记录
+  sources?: Record<string, FieldSource>; // 各字段的来源区域，键如 number、seller.name、items[0].amount
+  confidence?: Confidence; // 置信度
+  red_letter?: RedLetter | null; // 红字发票对应的正数发票
+  reversed_by?: number[]; // 冲红该发票的红字发票序号
+}
+
+// 红字发票对应的正数发票
+export interface RedLetter {
+  original_code: string; // 全电发票没有代码
+  original_number: string;
+  original_index: number; // 正数发票在本批次中的序号，0 表示不在本批次
+  original_batch: string; // 正数发票记入台账的批次
+}
+
+// 字段的来源区域，label_score 为值与标签的匹配程度（0~1）
+export interface FieldSource {
+  boxes: SourceBox[];
+  label_score: number;
+}
+
+// 置信度（0~1）：overall 为整张发票的置信度，fields 为各字段的置信度，键与 sources 一致
+export interface Confidence {
+  overall: number;
+  fields: Record<string, number>;
+}
+
+// 字段来源区域，页码从1开始，坐标与 TextItem 一致（原点在左上角，y 向下），y 为文本顶部
+export interface SourceBox {
+  page: number;
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+}
+
+// 人工修正记录
+export interface Correction {
+  item: number | null; // 明细行下标（从0开始），修正发票字段时为 null
+  field: string; // 如 number、seller.tax_code、total_amount，明细行为 name、amount、tax 等
+  original: string; // 解析得到的原始值
+  value: string;
+  time: string;
+  user: string;
+}
+
+// 金额交叉校验结果
+export interface ValidationFinding {
+  severity: "error" | "warning";
+  fields: string[];
+  message: string;
+}
+
+// 铁路电子客票信息
+export interface TrainTicket {
+  passenger_name: string;
+  passenger_id: string;
+  departure_station: string;
+  arrival_station: string;
+  train_number: string;
+  seat_class: string;
+  seat_number: string;
+  travel_date: string;
+  fare: string;
+  ticket_number: string;
+}
+
+// 航段信息
+export interface FlightSegment {
+  from: string;
+  to: string;
+  flight_number: string;
+  seat_class: string;
+  date: string;
+  time: string;
+}
+
+// 航空运输电子客票行程单信息
+export interface AirItinerary {
+  passenger_name: string;
+  passenger_id: string;
+  ticket_number: string;
+  segments: FlightSegment[];
+  fare: string;
+  development_fund: string;
+  fuel_surcharge: string;
+  other_taxes: string;
+  total: string;
 }
 
 // 文本位置信息
@@ -81,6 +184,8 @@ export async function parsePdfFile(
     
     // 读取文件为ArrayBuffer
     const arrayBuffer = await file.arrayBuffer();
+    // 计算文件哈希，需在 pdf.js 使用 ArrayBuffer 之前完成
+    const fileHash = await computeFileHash(arrayBuffer);
     progressCallback?.(10, 100); // 文件读取完成，进度10%
     
     // 加载PDF文档，添加cMapUrl和cMapPacked参数
@@ -139,7 +244,8 @@ export async function parsePdfFile(
     // 调用后端 Rust 代码解析发票信息
     const invoices = await invoke<Invoice[]>("parse_invoice_text", {
       filename: file.name,
-      pagesTextItems: allPagesTextItems
+      pagesTextItems: allPagesTextItems,
+      fileHash,
     });
     
     // 解析完成
@@ -154,6 +260,16 @@ export async function parsePdfFile(
   }
 }
 
+/**
+ * 计算文件内容的 SHA-256，用于识别重复文件
+ */
+async function computeFileHash(data: ArrayBuffer): Promise<string> {
+  const digest = await crypto.subtle.digest("SHA-256", data);
+  return Array.from(new Uint8Array(digest))
+    .map((byte) => byte.toString(16).padStart(2, "0"))
+    .join("");
+}
+
 /**
  * 创建空的发票对象
  */
@@ -199,6 +315,7 @@ function createEmptyInvoice(
     drawer: "",
     status,
     duplicate_info: "",
+    deductible: false,
   };
 }
 